@@ -0,0 +1,363 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::hardware::{EffectiveDuckDbSettings, HardwareInfo, HardwareSource};
+use crate::query_executor::PhaseTimes;
+
+/// How caches are treated between timed runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum BenchMode {
+    /// Reuse one warmed-up connection for every run (ANALYZE + full MV scans before timing)
+    #[default]
+    Warm,
+    /// Open a fresh database instance for every run, optionally dropping the OS page cache
+    Cold,
+    /// Time only the first execution of each query on a fresh, unwarmed database
+    FirstRun,
+}
+
+impl BenchMode {
+    pub fn label(&self) -> &'static str {
+        match self {
+            BenchMode::Warm => "warm",
+            BenchMode::Cold => "cold",
+            BenchMode::FirstRun => "first-run",
+        }
+    }
+}
+
+/// What a timed query execution covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum TimingMode {
+    /// Only `stmt.query([])`; results are written untimed on the first run
+    #[default]
+    Execute,
+    /// Time to last row: execution, draining and decoding every row, and CSV serialization
+    EndToEnd,
+}
+
+/// Latency distribution for one query, in milliseconds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryStats {
+    pub count: usize,
+    pub mean_ms: f64,
+    pub min_ms: f64,
+    pub max_ms: f64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub stddev_ms: f64,
+    /// Standard deviation relative to the mean
+    pub cv: f64,
+    /// Samples outside the Tukey fences (1.5 x IQR beyond the quartiles)
+    pub outliers: usize,
+}
+
+impl QueryStats {
+    pub fn from_samples(samples_ms: &[f64]) -> Self {
+        let mut sorted = samples_ms.to_vec();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+
+        let count = sorted.len();
+        let mean_ms = sorted.iter().sum::<f64>() / count.max(1) as f64;
+
+        // Sample standard deviation (n - 1)
+        let variance = if count > 1 {
+            sorted.iter().map(|v| (v - mean_ms).powi(2)).sum::<f64>() / (count - 1) as f64
+        } else {
+            0.0
+        };
+        let stddev_ms = variance.sqrt();
+        let cv = if mean_ms > 0.0 { stddev_ms / mean_ms } else { 0.0 };
+
+        let q1 = percentile(&sorted, 25.0);
+        let q3 = percentile(&sorted, 75.0);
+        let iqr = q3 - q1;
+        let (low_fence, high_fence) = (q1 - 1.5 * iqr, q3 + 1.5 * iqr);
+        let outliers = sorted.iter().filter(|&&v| v < low_fence || v > high_fence).count();
+
+        Self {
+            count,
+            mean_ms,
+            min_ms: sorted.first().copied().unwrap_or(0.0),
+            max_ms: sorted.last().copied().unwrap_or(0.0),
+            p50_ms: percentile(&sorted, 50.0),
+            p90_ms: percentile(&sorted, 90.0),
+            p99_ms: percentile(&sorted, 99.0),
+            stddev_ms,
+            cv,
+            outliers,
+        }
+    }
+}
+
+/// Nearest-rank percentile of an ascending slice
+pub fn percentile(sorted: &[f64], pct: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (pct / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Machine and engine the benchmark ran on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnvironmentInfo {
+    pub os: String,
+    pub arch: String,
+    pub num_threads: usize,
+    pub available_memory_gb: f64,
+    /// Where the thread count and memory came from (host, cgroup v1/v2)
+    #[serde(default)]
+    pub cpu_source: Option<HardwareSource>,
+    #[serde(default)]
+    pub memory_source: Option<HardwareSource>,
+    #[serde(default)]
+    pub disk_read_mb_s: Option<f64>,
+    pub scan_weight: f64,
+    pub rollup_weight: f64,
+    pub duckdb_version: String,
+    /// Settings in effect while building the database (None when reusing an existing one)
+    #[serde(default)]
+    pub preprocess_settings: Option<EffectiveDuckDbSettings>,
+    /// Settings in effect while running queries
+    #[serde(default)]
+    pub query_settings: Option<EffectiveDuckDbSettings>,
+}
+
+impl EnvironmentInfo {
+    pub fn collect(
+        hw: &HardwareInfo,
+        duckdb_version: String,
+        preprocess_settings: Option<EffectiveDuckDbSettings>,
+        query_settings: Option<EffectiveDuckDbSettings>,
+    ) -> Self {
+        let (scan_weight, rollup_weight) = hw.cost_weights();
+        Self {
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            num_threads: hw.num_threads,
+            available_memory_gb: hw.available_memory_gb,
+            cpu_source: Some(hw.cpu_source),
+            memory_source: Some(hw.memory_source),
+            disk_read_mb_s: hw.disk_read_mb_s,
+            scan_weight,
+            rollup_weight,
+            duckdb_version,
+            preprocess_settings,
+            query_settings,
+        }
+    }
+}
+
+/// Per-phase breakdown of end-to-end timings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhaseReport {
+    pub execution: QueryStats,
+    pub fetch: QueryStats,
+    pub serialization: QueryStats,
+    pub rows: usize,
+}
+
+impl PhaseReport {
+    pub fn from_times(times: &[PhaseTimes]) -> Self {
+        let phase = |f: fn(&PhaseTimes) -> f64| {
+            let samples_ms: Vec<f64> = times.iter().filter(|t| !t.timed_out).map(|t| f(t) * 1000.0).collect();
+            QueryStats::from_samples(&samples_ms)
+        };
+        Self {
+            execution: phase(|t| t.execute),
+            fetch: phase(|t| t.fetch),
+            serialization: phase(|t| t.serialize),
+            rows: times.iter().find(|t| !t.timed_out).map(|t| t.rows).unwrap_or(0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryReport {
+    pub query: usize,
+    pub sql: String,
+    /// MV the planner routed the query to, or None for a raw `events` scan
+    pub mv: Option<String>,
+    /// Planner translation plus DuckDB prepare
+    #[serde(default)]
+    pub planning_ms: f64,
+    /// Distribution of the completed runs; end-to-end totals when timing is end-to-end
+    pub stats: QueryStats,
+    /// Runs interrupted by --query-timeout or --global-timeout
+    #[serde(default)]
+    pub timeouts: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phases: Option<PhaseReport>,
+    /// Throughput while other queries ran concurrently (parallel mode only)
+    pub qps: Option<f64>,
+    pub samples_ms: Vec<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkReport {
+    pub created_at: String,
+    pub queries_file: String,
+    #[serde(default)]
+    pub mode: BenchMode,
+    #[serde(default)]
+    pub timing: TimingMode,
+    pub runs: usize,
+    pub connections: usize,
+    pub environment: EnvironmentInfo,
+    pub queries: Vec<QueryReport>,
+    pub sum_of_averages_ms: f64,
+    pub execution_time_ms: f64,
+    /// Aggregate throughput (parallel mode only)
+    pub qps: Option<f64>,
+}
+
+impl BenchmarkReport {
+    pub fn write(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+}
+
+/// Change in one query's latency between a saved report and the current run
+pub struct QueryDelta {
+    pub query: usize,
+    pub previous_p50_ms: f64,
+    pub current_p50_ms: f64,
+    /// Relative change of the median, in percent (positive = slower)
+    pub delta_pct: f64,
+    /// Two-sided Mann-Whitney U p-value for the two sample sets
+    pub p_value: f64,
+    pub regressed: bool,
+    pub improved: bool,
+    /// Too few samples on either side for any difference to be significant at `alpha`
+    pub insufficient_samples: bool,
+    /// The planner picked a different MV than in the saved report
+    pub mv_changed: bool,
+}
+
+/// Compare each query present in both reports. A query regresses when its median is more than
+/// `threshold_pct` slower and the difference is significant at `alpha`; improvements are flagged
+/// symmetrically.
+pub fn compare_reports(previous: &BenchmarkReport, current: &BenchmarkReport, threshold_pct: f64, alpha: f64) -> Vec<QueryDelta> {
+    let mut deltas = Vec::new();
+    for cur in &current.queries {
+        let Some(prev) = previous.queries.iter().find(|q| q.query == cur.query) else {
+            continue;
+        };
+
+        // A query that never completed now but did before is always a regression
+        if cur.stats.count == 0 {
+            deltas.push(QueryDelta {
+                query: cur.query,
+                previous_p50_ms: prev.stats.p50_ms,
+                current_p50_ms: f64::INFINITY,
+                delta_pct: f64::INFINITY,
+                p_value: 0.0,
+                regressed: prev.stats.count > 0,
+                improved: false,
+                insufficient_samples: false,
+                mv_changed: prev.mv != cur.mv,
+            });
+            continue;
+        }
+
+        let previous_p50_ms = prev.stats.p50_ms;
+        let current_p50_ms = cur.stats.p50_ms;
+        let delta_pct = if previous_p50_ms > 0.0 {
+            (current_p50_ms - previous_p50_ms) / previous_p50_ms * 100.0
+        } else {
+            0.0
+        };
+        let p_value = mann_whitney_u(&prev.samples_ms, &cur.samples_ms);
+        let significant = p_value < alpha;
+
+        deltas.push(QueryDelta {
+            query: cur.query,
+            previous_p50_ms,
+            current_p50_ms,
+            delta_pct,
+            p_value,
+            regressed: significant && delta_pct > threshold_pct,
+            improved: significant && delta_pct < -threshold_pct,
+            insufficient_samples: !can_reach_significance(prev.samples_ms.len(), cur.samples_ms.len(), alpha),
+            mv_changed: prev.mv != cur.mv,
+        });
+    }
+    deltas
+}
+
+/// Two-sided p-value of the Mann-Whitney U test, using the normal approximation with tie
+/// correction. Returns 1.0 when either side has too few samples to say anything.
+pub fn mann_whitney_u(a: &[f64], b: &[f64]) -> f64 {
+    let (n1, n2) = (a.len(), b.len());
+    if n1 < 2 || n2 < 2 {
+        return 1.0;
+    }
+
+    let mut combined: Vec<(f64, bool)> = a.iter().map(|&v| (v, true)).chain(b.iter().map(|&v| (v, false))).collect();
+    combined.sort_by(|x, y| x.0.total_cmp(&y.0));
+
+    // Assign average ranks to ties and accumulate the tie correction term
+    let n = combined.len();
+    let mut rank_sum_a = 0.0;
+    let mut tie_term = 0.0;
+    let mut i = 0;
+    while i < n {
+        let mut j = i;
+        while j + 1 < n && combined[j + 1].0 == combined[i].0 {
+            j += 1;
+        }
+        let avg_rank = (i + j) as f64 / 2.0 + 1.0;
+        let ties = (j - i + 1) as f64;
+        tie_term += ties.powi(3) - ties;
+        rank_sum_a += combined[i..=j].iter().filter(|(_, from_a)| *from_a).count() as f64 * avg_rank;
+        i = j + 1;
+    }
+
+    let (n1, n2, n) = (n1 as f64, n2 as f64, n as f64);
+    let u = rank_sum_a - n1 * (n1 + 1.0) / 2.0;
+    let mean = n1 * n2 / 2.0;
+    let variance = n1 * n2 / 12.0 * ((n + 1.0) - tie_term / (n * (n - 1.0)));
+    if variance <= 0.0 {
+        return 1.0;
+    }
+
+    // Continuity correction
+    let z = ((u - mean).abs() - 0.5).max(0.0) / variance.sqrt();
+    (2.0 * (1.0 - normal_cdf(z))).min(1.0)
+}
+
+/// Whether `mann_whitney_u` can return a p-value below `alpha` for these sample sizes at all:
+/// the smallest p comes from two completely separated sets without ties. With the normal
+/// approximation two-sided p < 0.05 needs at least 4 samples per side.
+pub fn can_reach_significance(n1: usize, n2: usize, alpha: f64) -> bool {
+    let a: Vec<f64> = (0..n1).map(|i| i as f64).collect();
+    let b: Vec<f64> = (n1..n1 + n2).map(|i| i as f64).collect();
+    mann_whitney_u(&a, &b) < alpha
+}
+
+fn normal_cdf(z: f64) -> f64 {
+    0.5 * (1.0 + erf(z / std::f64::consts::SQRT_2))
+}
+
+/// Abramowitz and Stegun 7.1.26 (max error 1.5e-7)
+fn erf(x: f64) -> f64 {
+    let sign = if x < 0.0 { -1.0 } else { 1.0 };
+    let x = x.abs();
+    let t = 1.0 / (1.0 + 0.3275911 * x);
+    let poly = t * (0.254829592 + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    sign * (1.0 - poly * (-x * x).exp())
+}
@@ -0,0 +1,146 @@
+use std::path::{Path, PathBuf};
+
+use crate::hardware::HardwareSource;
+
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// cgroup v1 reports "no limit" as a page-aligned i64::MAX; anything this large is unlimited
+const V1_UNLIMITED: u64 = 1 << 60;
+
+/// Resource limits of the cgroup this process runs in
+pub struct CgroupLimits {
+    pub source: HardwareSource,
+    /// CPU quota in cores (quota / period), None when unlimited
+    pub cpu_quota: Option<f64>,
+    /// Memory left under the limit in bytes, None when unlimited
+    pub memory_available_bytes: Option<u64>,
+}
+
+/// Read the CPU and memory limits of our own cgroup, or None outside of any cgroup hierarchy
+pub fn detect() -> Option<CgroupLimits> {
+    let self_cgroup = std::fs::read_to_string("/proc/self/cgroup").ok()?;
+    if Path::new(CGROUP_ROOT).join("cgroup.controllers").exists() {
+        detect_v2(&self_cgroup)
+    } else {
+        detect_v1(&self_cgroup)
+    }
+}
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    std::fs::read_to_string(path).ok().map(|s| s.trim().to_string())
+}
+
+fn read_u64(path: &Path) -> Option<u64> {
+    read_trimmed(path)?.parse().ok()
+}
+
+/// Value of `key` in a `memory.stat` style "key value" file
+fn read_stat(path: &Path, key: &str) -> Option<u64> {
+    let content = std::fs::read_to_string(path).ok()?;
+    content.lines().find_map(|line| {
+        let (k, v) = line.split_once(' ')?;
+        if k == key { v.trim().parse().ok() } else { None }
+    })
+}
+
+/// Limit minus the working set. Inactive page cache is reclaimable, so it is not counted as used
+/// (the same working-set definition the kubelet uses for eviction).
+fn available_under_limit(limit: u64, usage: u64, inactive_file: u64) -> u64 {
+    limit.saturating_sub(usage.saturating_sub(inactive_file))
+}
+
+/// Directory of our cgroup under `mount`. Inside a cgroup namespace the path in
+/// /proc/self/cgroup is not visible and the mount itself is our cgroup.
+fn cgroup_dir(mount: &Path, cgroup_path: &str) -> PathBuf {
+    let dir = mount.join(cgroup_path.trim_start_matches('/'));
+    if dir.exists() { dir } else { mount.to_path_buf() }
+}
+
+fn detect_v2(self_cgroup: &str) -> Option<CgroupLimits> {
+    let cgroup_path = self_cgroup.lines().find_map(|line| line.strip_prefix("0::"))?;
+    let root = Path::new(CGROUP_ROOT);
+    let leaf = cgroup_dir(root, cgroup_path);
+
+    // Limits on any ancestor apply too, so take the tightest one on the way up to the root
+    let mut cpu_quota: Option<f64> = None;
+    let mut memory_available_bytes: Option<u64> = None;
+    let mut dir = Some(leaf.as_path());
+    while let Some(current) = dir {
+        if let Some(cpu_max) = read_trimmed(&current.join("cpu.max"))
+            && let Some((quota, period)) = cpu_max.split_once(' ')
+            && let (Ok(quota), Ok(period)) = (quota.parse::<f64>(), period.parse::<f64>())
+            && period > 0.0
+        {
+            let cores = quota / period;
+            cpu_quota = Some(cpu_quota.map_or(cores, |c| c.min(cores)));
+        }
+
+        if let Some(limit) = read_u64(&current.join("memory.max")) {
+            let usage = read_u64(&current.join("memory.current")).unwrap_or(0);
+            let inactive_file = read_stat(&current.join("memory.stat"), "inactive_file").unwrap_or(0);
+            let available = available_under_limit(limit, usage, inactive_file);
+            memory_available_bytes = Some(memory_available_bytes.map_or(available, |m| m.min(available)));
+        }
+
+        if current == root {
+            break;
+        }
+        dir = current.parent().filter(|p| p.starts_with(root));
+    }
+
+    Some(CgroupLimits {
+        source: HardwareSource::CgroupV2,
+        cpu_quota,
+        memory_available_bytes,
+    })
+}
+
+fn detect_v1(self_cgroup: &str) -> Option<CgroupLimits> {
+    // Lines look like "4:memory:/kubepods/pod.../container"
+    let controller_path = |controller: &str| {
+        self_cgroup.lines().find_map(|line| {
+            let mut parts = line.splitn(3, ':');
+            let (_, controllers, path) = (parts.next()?, parts.next()?, parts.next()?);
+            controllers.split(',').any(|c| c == controller).then_some(path)
+        })
+    };
+
+    let root = Path::new(CGROUP_ROOT);
+    let mut found = false;
+
+    let cpu_quota = controller_path("cpu").and_then(|path| {
+        let mount = ["cpu", "cpu,cpuacct", "cpuacct,cpu"]
+            .iter()
+            .map(|m| root.join(m))
+            .find(|m| m.exists())?;
+        found = true;
+        let dir = cgroup_dir(&mount, path);
+        let quota: i64 = read_trimmed(&dir.join("cpu.cfs_quota_us"))?.parse().ok()?;
+        let period = read_u64(&dir.join("cpu.cfs_period_us"))?;
+        (quota > 0 && period > 0).then(|| quota as f64 / period as f64)
+    });
+
+    let memory_available_bytes = controller_path("memory").and_then(|path| {
+        let mount = root.join("memory");
+        if !mount.exists() {
+            return None;
+        }
+        found = true;
+        let dir = cgroup_dir(&mount, path);
+        let stat = dir.join("memory.stat");
+        // hierarchical_memory_limit already accounts for limits set on ancestors
+        let limit = read_stat(&stat, "hierarchical_memory_limit").or_else(|| read_u64(&dir.join("memory.limit_in_bytes")))?;
+        if limit >= V1_UNLIMITED {
+            return None;
+        }
+        let usage = read_u64(&dir.join("memory.usage_in_bytes")).unwrap_or(0);
+        let inactive_file = read_stat(&stat, "total_inactive_file").unwrap_or(0);
+        Some(available_under_limit(limit, usage, inactive_file))
+    });
+
+    found.then_some(CgroupLimits {
+        source: HardwareSource::CgroupV1,
+        cpu_quota,
+        memory_available_bytes,
+    })
+}
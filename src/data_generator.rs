@@ -0,0 +1,180 @@
+use anyhow::Result;
+use indicatif::ProgressBar;
+use std::path::Path;
+
+use crate::rng::Rng;
+use crate::schema::EventSchema;
+
+/// Event types with their share of all events: most auctions are served, fewer
+/// become impressions, a few get clicked and very few convert
+const EVENT_TYPES: &[(&str, f64)] = &[("serve", 0.50), ("impression", 0.40), ("click", 0.09), ("purchase", 0.01)];
+
+const COUNTRIES: &[&str] = &[
+    "US", "IN", "BR", "GB", "DE", "JP", "FR", "CA", "MX", "ID", "KR", "AU", "IT", "ES", "TR", "PH", "VN", "TH", "PL", "NL",
+];
+
+/// Relative traffic per hour of day (UTC), peaking in the evening
+const HOURLY_WEIGHTS: [f64; 24] = [
+    0.6, 0.5, 0.4, 0.35, 0.3, 0.35, 0.5, 0.7, 0.85, 0.95, 1.0, 1.05, 1.1, 1.1, 1.05, 1.05, 1.1, 1.2, 1.35, 1.5, 1.55, 1.4,
+    1.1, 0.8,
+];
+
+const MS_PER_DAY: i64 = 86_400_000;
+
+/// Shape of the generated dataset
+pub struct GeneratorConfig {
+    pub rows: usize,
+    /// Number of `events_part_*.csv` files to spread the rows over
+    pub parts: usize,
+    pub seed: u64,
+    /// Timestamps are spread over this many days starting at `start_ms`
+    pub days: usize,
+    /// Unix epoch milliseconds of the first day
+    pub start_ms: i64,
+    pub advertisers: usize,
+    pub publishers: usize,
+    pub users: usize,
+}
+
+impl GeneratorConfig {
+    /// Entity counts grow with the row count so group sizes stay realistic at any scale
+    pub fn scaled(rows: usize, parts: usize, seed: u64, days: usize) -> Self {
+        Self {
+            rows,
+            parts: parts.max(1),
+            seed,
+            days: days.max(1),
+            start_ms: 1_717_200_000_000, // 2024-06-01 00:00 UTC
+            advertisers: (rows / 2_000).clamp(20, 5_000),
+            publishers: (rows / 5_000).clamp(10, 1_000),
+            users: (rows / 10).max(100),
+        }
+    }
+}
+
+/// Discrete distribution over indices 0..n, sampled by inverting the cumulative weights
+struct Discrete {
+    cumulative: Vec<f64>,
+}
+
+impl Discrete {
+    /// Zipf over ranks with exponent `s`: index 0 is the most frequent
+    fn zipf(n: usize, s: f64) -> Self {
+        let mut total = 0.0;
+        let cumulative = (1..=n.max(1))
+            .map(|rank| {
+                total += 1.0 / (rank as f64).powf(s);
+                total
+            })
+            .collect::<Vec<_>>();
+        Self {
+            cumulative: cumulative.iter().map(|c| c / total).collect(),
+        }
+    }
+
+    fn weighted(weights: &[f64]) -> Self {
+        let total: f64 = weights.iter().sum();
+        let mut running = 0.0;
+        Self {
+            cumulative: weights
+                .iter()
+                .map(|w| {
+                    running += w / total;
+                    running
+                })
+                .collect(),
+        }
+    }
+
+    fn sample(&self, rng: &mut Rng) -> usize {
+        let u = rng.next_f64();
+        self.cumulative.partition_point(|&c| c < u).min(self.cumulative.len() - 1)
+    }
+}
+
+/// Standard normal via Box-Muller
+fn normal(rng: &mut Rng) -> f64 {
+    let u1 = rng.next_f64().max(f64::MIN_POSITIVE);
+    let u2 = rng.next_f64();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+}
+
+/// Write `events_part_0.csv` .. `events_part_{parts-1}.csv` into `dir`, in the raw format
+/// `data_loader` reads: epoch-millisecond `ts`, empty prices where they do not apply.
+/// Output depends only on the config, so the same seed always yields the same files. The
+/// columns are those of `EventSchema::ad_events`; any other active schema is rejected.
+pub fn generate_events(dir: &Path, config: &GeneratorConfig, pb: &ProgressBar) -> Result<()> {
+    if *crate::schema::get() != EventSchema::ad_events() {
+        anyhow::bail!("Synthetic data follows the ad events schema; it cannot be generated for a custom event schema");
+    }
+    std::fs::create_dir_all(dir)?;
+
+    let types = Discrete::weighted(&EVENT_TYPES.iter().map(|(_, w)| *w).collect::<Vec<_>>());
+    let hours = Discrete::weighted(&HOURLY_WEIGHTS);
+    let countries = Discrete::zipf(COUNTRIES.len(), 1.2);
+    let advertisers = Discrete::zipf(config.advertisers, 1.1);
+    let publishers = Discrete::zipf(config.publishers, 1.0);
+
+    let rows_per_part = config.rows.div_ceil(config.parts);
+    for part in 0..config.parts {
+        // Each part has its own stream so parts do not depend on each other
+        let mut rng = Rng::new(config.seed ^ (part as u64).wrapping_mul(0xA24B_AED4_963E_E407));
+        let path = dir.join(format!("events_part_{}.csv", part));
+        let mut wtr = csv::Writer::from_path(&path)?;
+        wtr.write_record([
+            "ts",
+            "type",
+            "auction_id",
+            "advertiser_id",
+            "publisher_id",
+            "bid_price",
+            "user_id",
+            "total_price",
+            "country",
+        ])?;
+
+        let part_rows = rows_per_part.min(config.rows.saturating_sub(part * rows_per_part));
+        for _ in 0..part_rows {
+            let event_type = EVENT_TYPES[types.sample(&mut rng)].0;
+
+            let day = rng.below(config.days) as i64;
+            let hour = hours.sample(&mut rng) as i64;
+            let ms_in_hour = rng.below(3_600_000) as i64;
+            let ts = config.start_ms + day * MS_PER_DAY + hour * 3_600_000 + ms_in_hour;
+
+            let auction_id = format!("{:016x}{:016x}", rng.next_u64(), rng.next_u64());
+            let advertiser_id = 1 + advertisers.sample(&mut rng);
+            let publisher_id = 1 + publishers.sample(&mut rng);
+            let user_id = 1 + rng.below(config.users);
+            let country = COUNTRIES[countries.sample(&mut rng)];
+
+            // Log-normal prices: bids are small CPM-style amounts, purchases are order totals
+            let bid_price = if event_type == "impression" {
+                format!("{:.6}", (normal(&mut rng) * 0.8 - 1.5).exp())
+            } else {
+                String::new()
+            };
+            let total_price = if event_type == "purchase" {
+                format!("{:.2}", (normal(&mut rng) * 0.9 + 3.5).exp())
+            } else {
+                String::new()
+            };
+
+            wtr.write_record([
+                ts.to_string(),
+                event_type.to_string(),
+                auction_id,
+                advertiser_id.to_string(),
+                publisher_id.to_string(),
+                bid_price,
+                user_id.to_string(),
+                total_price,
+                country.to_string(),
+            ])?;
+        }
+        wtr.flush()?;
+        pb.inc(1);
+    }
+
+    Ok(())
+}
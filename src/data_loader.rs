@@ -1,18 +1,230 @@
 use duckdb::Connection;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use anyhow::Result;
 
-pub fn load_data(con: &Connection, data_dir: &PathBuf) -> Result<Option<PathBuf>> {
+use crate::data_quality::{QualityOptions, QualityReport, check_raw_events, checked_relation_sql, failed_cast_flags};
+use crate::schema::{ColumnType, DERIVED_TIME_COLUMNS, EventSchema};
+
+/// Format of the raw event files, selectable with `--input-format` (detected when omitted)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum InputFormat {
+    Csv,
+    CsvGz,
+    Ndjson,
+    Parquet,
+}
+
+impl InputFormat {
+    /// Detection order when several formats are present in the input directory
+    const ALL: [InputFormat; 4] = [InputFormat::Csv, InputFormat::CsvGz, InputFormat::Ndjson, InputFormat::Parquet];
+
+    /// Files matched when `--input-glob` is not given
+    pub fn default_glob(&self) -> &'static str {
+        match self {
+            InputFormat::Csv => "events_part_*.csv",
+            InputFormat::CsvGz => "events_part_*.csv.gz",
+            InputFormat::Ndjson => "events_part_*.ndjson",
+            InputFormat::Parquet => "*.parquet",
+        }
+    }
+
+    /// Extensions that identify this format when inferring it from a glob
+    fn matches_extension(&self, name: &str) -> bool {
+        match self {
+            InputFormat::Csv => name.ends_with(".csv"),
+            InputFormat::CsvGz => name.ends_with(".csv.gz"),
+            InputFormat::Ndjson => name.ends_with(".ndjson") || name.ends_with(".jsonl") || name.ends_with(".json"),
+            InputFormat::Parquet => name.ends_with(".parquet"),
+        }
+    }
+
+    /// SELECT over the files matching `pattern` that yields every raw column as VARCHAR
+    fn raw_select(&self, schema: &EventSchema, pattern: &str) -> String {
+        let pattern = escape_literal(pattern);
+        let columns = schema
+            .columns
+            .iter()
+            .map(|c| format!("'{}': 'VARCHAR'", c.name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        match self {
+            InputFormat::Csv => format!(
+                "SELECT * FROM read_csv('{}', AUTO_DETECT = FALSE, HEADER = TRUE, union_by_name = TRUE, COLUMNS = {{{}}})",
+                pattern, columns
+            ),
+            InputFormat::CsvGz => format!(
+                "SELECT * FROM read_csv('{}', AUTO_DETECT = FALSE, HEADER = TRUE, union_by_name = TRUE, COMPRESSION = 'gzip', COLUMNS = {{{}}})",
+                pattern, columns
+            ),
+            InputFormat::Ndjson => format!(
+                "SELECT * FROM read_json('{}', format = 'newline_delimited', union_by_name = TRUE, columns = {{{}}})",
+                pattern, columns
+            ),
+            // Parquet drops carry their own types; flatten them so the casts below apply uniformly
+            InputFormat::Parquet => format!(
+                "SELECT {} FROM read_parquet('{}', union_by_name = TRUE)",
+                schema
+                    .columns
+                    .iter()
+                    .map(|c| format!("CAST({0} AS VARCHAR) AS {0}", c.name))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                pattern
+            ),
+        }
+    }
+}
+
+/// Match a file name against a glob with at most one `*`, enough for the default globs
+fn glob_matches(glob: &str, name: &str) -> bool {
+    match glob.split_once('*') {
+        Some((prefix, suffix)) => name.len() >= prefix.len() + suffix.len() && name.starts_with(prefix) && name.ends_with(suffix),
+        None => glob == name,
+    }
+}
+
+/// Where the raw events come from: a format and the file glob to read
+#[derive(Debug, Clone)]
+pub struct InputSource {
+    pub format: InputFormat,
+    /// Glob over the input files, e.g. `data/data/events_part_*.csv`
+    pub pattern: String,
+}
+
+impl InputSource {
+    /// Resolve `--input-format` and `--input-glob` against `dir`. A relative glob is taken
+    /// relative to `dir`. Without a format it is inferred from the glob's extension, or else
+    /// from which format's default glob matches files in `dir`.
+    pub fn resolve(dir: &Path, format: Option<InputFormat>, glob: Option<&str>) -> Result<Self> {
+        let format = match (format, glob) {
+            (Some(format), _) => format,
+            (None, Some(glob)) => InputFormat::ALL
+                .into_iter()
+                .find(|f| f.matches_extension(glob))
+                .ok_or_else(|| anyhow::anyhow!("Cannot infer the input format from glob `{}`; pass --input-format", glob))?,
+            (None, None) => Self::detect_format(dir)?,
+        };
+
+        let glob = glob.unwrap_or(format.default_glob());
+        let pattern = if Path::new(glob).is_absolute() {
+            glob.to_string()
+        } else {
+            dir.join(glob).to_string_lossy().to_string()
+        };
+        Ok(Self { format, pattern })
+    }
+
+    fn detect_format(dir: &Path) -> Result<InputFormat> {
+        let names: Vec<String> = std::fs::read_dir(dir)
+            .map_err(|e| anyhow::anyhow!("Cannot read input directory {}: {}", dir.display(), e))?
+            .flatten()
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .collect();
+
+        InputFormat::ALL
+            .into_iter()
+            .find(|format| names.iter().any(|name| glob_matches(format.default_glob(), name)))
+            .ok_or_else(|| anyhow::anyhow!("No CSV, gzip CSV, NDJSON or Parquet event files in {}", dir.display()))
+    }
+}
+
+impl std::fmt::Display for InputSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let format = match self.format {
+            InputFormat::Csv => "CSV",
+            InputFormat::CsvGz => "gzip CSV",
+            InputFormat::Ndjson => "NDJSON",
+            InputFormat::Parquet => "Parquet",
+        };
+        write!(f, "{} ({})", self.pattern, format)
+    }
+}
+
+/// Paths go into single-quoted SQL string literals
+fn escape_literal(value: &str) -> String {
+    value.replace('\'', "''")
+}
+
+/// Timestamp, derived time columns, the other columns, then `extra`, over a relation that
+/// already has typed columns
+fn derived_columns_sql(schema: &EventSchema, relation: &str, extra: &[String]) -> String {
+    let ts = &schema.timestamp_column;
+    let mut select = vec![ts.clone()];
+    for (name, expr) in DERIVED_TIME_COLUMNS {
+        select.push(format!("{} AS {}", expr.replace("{ts}", ts), name));
+    }
+    select.extend(schema.columns.iter().filter(|c| &c.name != ts).map(|c| c.name.clone()));
+    select.extend(extra.iter().cloned());
+    format!("SELECT\n  {}\nFROM {}", select.join(",\n  "), relation)
+}
+
+/// The `raw_events` view: every schema column of the raw source as VARCHAR
+fn raw_events_view_sql(schema: &EventSchema, source: &InputSource) -> String {
+    format!(
+        "CREATE OR REPLACE VIEW raw_events AS {}",
+        source.format.raw_select(schema, &source.pattern)
+    )
+}
+
+/// What the Parquet cache is written from: the `raw_events` columns cast once, with derived
+/// columns, plus the `__failed_<col>` flags the data-quality report counts
+fn cache_source_sql(schema: &EventSchema) -> String {
+    let mut casted: Vec<String> = schema.columns.iter().map(|c| format!("__typed_{0} AS {0}", c.name)).collect();
+    casted.extend(failed_cast_flags(schema));
+    let flags: Vec<String> = schema
+        .columns
+        .iter()
+        .filter(|c| c.column_type != ColumnType::Varchar)
+        .map(|c| format!("__failed_{}", c.name))
+        .collect();
+    format!(
+        "{},\ncasted AS (\n  SELECT\n    {}\n  FROM checked\n)\n{}",
+        checked_relation_sql(schema),
+        casted.join(",\n    "),
+        derived_columns_sql(schema, "casted", &flags)
+    )
+}
+
+/// The `events` view over the typed Parquet cache written by `load_data`
+fn parquet_events_view_sql(schema: &EventSchema, parquet_pattern: &str) -> String {
+    format!(
+        "CREATE OR REPLACE VIEW events AS {}",
+        derived_columns_sql(schema, &format!("read_parquet('{}')", escape_literal(parquet_pattern)), &[])
+    )
+}
+
+/// What `load_data` did
+#[derive(Debug, Clone)]
+pub struct LoadSummary {
+    /// Parquet cache the `events` view reads
+    pub parquet_dir: Option<PathBuf>,
+    /// Data-quality report of the raw files, or None when the Parquet cache was reused
+    pub quality: Option<QualityReport>,
+}
+
+/// Create the `events` view. The first load reads the raw files under `data_dir` (format and
+/// glob resolved by `InputSource::resolve`, columns from the event schema) once, caching them as
+/// Parquet next to `data_dir` and checking their data quality from that cache (failing above
+/// `quality.max_bad_rows_pct`, without keeping the cache); later loads read that cache.
+pub fn load_data(
+    con: &Connection,
+    data_dir: &Path,
+    input_format: Option<InputFormat>,
+    input_glob: Option<&str>,
+    quality: &QualityOptions,
+) -> Result<LoadSummary> {
+    let schema = crate::schema::get();
+
     // Determine parquet file/directory location (in data directory parent)
     let parquet_dir = data_dir.parent()
         .unwrap_or(data_dir)
         .join("events.parquet");
-    
+
     // Check if parquet already exists
     let parquet_exists = parquet_dir.is_dir() || parquet_dir.exists();
-    
-    let parquet_path = if parquet_exists {
-        // Parquet exists - use it directly, skip CSV entirely
+
+    let (parquet_dir, quality) = if parquet_exists {
+        // Parquet exists - use it directly, skip the raw source entirely
         let parquet_pattern = if parquet_dir.is_dir() {
             // Directory with multiple parquet files - use glob pattern
             format!("{}/data_*.parquet", parquet_dir.to_string_lossy())
@@ -22,146 +234,68 @@ pub fn load_data(con: &Connection, data_dir: &PathBuf) -> Result<Option<PathBuf>
         };
 
         // Create events view directly from Parquet
-        con.execute(
-            &format!(
-                r#"
-                CREATE OR REPLACE VIEW events AS
-                SELECT
-                  ts,
-                  DATE_TRUNC('week', ts)              AS week,
-                  DATE(ts)                            AS day,
-                  DATE_TRUNC('hour', ts)              AS hour,
-                  STRFTIME(ts, '%Y-%m-%d %H:%M')      AS minute,
-                  type,
-                  auction_id,
-                  advertiser_id,
-                  publisher_id,
-                  bid_price,
-                  user_id,
-                  total_price,
-                  country
-                FROM read_parquet('{}')
-                "#,
-                parquet_pattern
-            ),
-            [],
-        )?;
-        
-        Some(parquet_dir)
+        con.execute(&parquet_events_view_sql(schema, &parquet_pattern), [])?;
+
+        (Some(parquet_dir), None)
     } else {
-        // Parquet doesn't exist - need to generate it from CSV
-        let csv_pattern = format!("{}/events_part_*.csv", data_dir.to_string_lossy());
-        
-        // Create the events view from CSV first
-        con.execute(
-            &format!(
-                r#"
-                CREATE OR REPLACE VIEW events AS
-                WITH raw AS (
-                  SELECT *
-                  FROM read_csv(
-                    '{}',
-                    AUTO_DETECT = FALSE,
-                    HEADER = TRUE,
-                    union_by_name = TRUE,
-                    COLUMNS = {{
-                      'ts': 'VARCHAR',
-                      'type': 'VARCHAR',
-                      'auction_id': 'VARCHAR',
-                      'advertiser_id': 'VARCHAR',
-                      'publisher_id': 'VARCHAR',
-                      'bid_price': 'VARCHAR',
-                      'user_id': 'VARCHAR',
-                      'total_price': 'VARCHAR',
-                      'country': 'VARCHAR'
-                    }}
-                  )
-                ),
-                casted AS (
-                  SELECT
-                    to_timestamp(TRY_CAST(ts AS DOUBLE) / 1000.0)    AS ts,
-                    TRY_CAST(type AS ENUM('impression','serve','click','purchase')) AS type,
-                    auction_id,
-                    TRY_CAST(advertiser_id AS INTEGER)        AS advertiser_id,
-                    TRY_CAST(publisher_id AS INTEGER)        AS publisher_id,
-                    NULLIF(bid_price, '')::DOUBLE             AS bid_price,
-                    TRY_CAST(user_id AS BIGINT)               AS user_id,
-                    NULLIF(total_price, '')::DOUBLE           AS total_price,
-                    country
-                  FROM raw
-                )
-                SELECT
-                  ts,
-                  DATE_TRUNC('week', ts)              AS week,
-                  DATE(ts)                            AS day,
-                  DATE_TRUNC('hour', ts)              AS hour,
-                  STRFTIME(ts, '%Y-%m-%d %H:%M')      AS minute,
-                  type,
-                  auction_id,
-                  advertiser_id,
-                  publisher_id,
-                  bid_price,
-                  user_id,
-                  total_price,
-                  country
-                FROM casted
-                "#,
-                csv_pattern
-            ),
-            [],
-        )?;
+        // Parquet doesn't exist - need to generate it from the raw source
+        let source = InputSource::resolve(data_dir, input_format, input_glob)?;
+        con.execute(&raw_events_view_sql(schema, &source), [])?;
 
-        // Generate parquet from CSV view
+        // Generate parquet from raw view
         // Use hardware-aware Parquet generation
         use crate::hardware::get_hardware_info;
         let hw = get_hardware_info();
-        
+
         // Estimate total rows (rough estimate: 245M for full dataset)
         let estimated_rows = 245_000_000; // Full dataset estimate
         let optimal_row_group_size = hw.optimal_row_group_size(estimated_rows);
-        
+
         // Create directory if it doesn't exist
         std::fs::create_dir_all(&parquet_dir)?;
-        let parquet_file = parquet_dir.join("data.parquet");
-        
+
+        // PER_THREAD_OUTPUT treats the target as a directory and writes data_0.parquet, data_1.parquet, ...
         con.execute(
             &format!(
-                "COPY (SELECT * FROM events) TO '{}' (FORMAT PARQUET, COMPRESSION ZSTD, PER_THREAD_OUTPUT, ROW_GROUP_SIZE {});",
-                parquet_file.to_string_lossy(),
+                "COPY ({}) TO '{}' (FORMAT PARQUET, COMPRESSION ZSTD, PER_THREAD_OUTPUT, ROW_GROUP_SIZE {});",
+                cache_source_sql(schema),
+                escape_literal(&parquet_dir.to_string_lossy()),
                 optimal_row_group_size
             ),
             [],
         )?;
-        
-        // Replace events view to read from Parquet
         let parquet_pattern = format!("{}/data_*.parquet", parquet_dir.to_string_lossy());
-        con.execute(
-            &format!(
-                r#"
-                CREATE OR REPLACE VIEW events AS
-                SELECT
-                  ts,
-                  DATE_TRUNC('week', ts)              AS week,
-                  DATE(ts)                            AS day,
-                  DATE_TRUNC('hour', ts)              AS hour,
-                  STRFTIME(ts, '%Y-%m-%d %H:%M')      AS minute,
-                  type,
-                  auction_id,
-                  advertiser_id,
-                  publisher_id,
-                  bid_price,
-                  user_id,
-                  total_price,
-                  country
-                FROM read_parquet('{}')
-                "#,
-                parquet_pattern
-            ),
-            [],
-        )?;
 
-        Some(parquet_dir)
+        // The cache holds each raw value's cast outcome, so the report counts from it; a
+        // rejected load must not leave a cache behind for the next run to reuse silently
+        let report = check_raw_events(
+            con,
+            schema,
+            &format!("read_parquet('{}')", escape_literal(&parquet_pattern)),
+            &source.to_string(),
+            quality,
+        )
+        .and_then(|report| {
+            if let Some(path) = &quality.report_path {
+                report.write(path)?;
+            }
+            report.enforce(quality)?;
+            Ok(report)
+        });
+        let report = match report {
+            Ok(report) => report,
+            Err(err) => {
+                std::fs::remove_dir_all(&parquet_dir)?;
+                return Err(err);
+            }
+        };
+
+        // Replace events view to read from Parquet
+        con.execute(&parquet_events_view_sql(schema, &parquet_pattern), [])?;
+        con.execute("DROP VIEW IF EXISTS raw_events", [])?;
+
+        (Some(parquet_dir), Some(report))
     };
-    
-    Ok(parquet_path)
-}
\ No newline at end of file
+
+    Ok(LoadSummary { parquet_dir, quality })
+}
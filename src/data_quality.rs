@@ -0,0 +1,340 @@
+use anyhow::Result;
+use duckdb::Connection;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::schema::{ColumnDef, ColumnType, EventSchema};
+
+/// Event times (UTC) before this are out of range unless `--min-event-time` says otherwise
+pub const DEFAULT_MIN_EVENT_TIME: &str = "2000-01-01 00:00:00";
+
+/// Unknown enum values listed per column, most frequent first
+const TOP_UNKNOWN_VALUES: usize = 10;
+
+/// What the ingestion data-quality report checks and when it fails the load
+#[derive(Debug, Clone)]
+pub struct QualityOptions {
+    /// Event times before this UTC time are out of range
+    pub min_event_time: String,
+    /// Event times after this UTC time are out of range
+    pub max_event_time: String,
+    /// Offending raw rows to include in the report
+    pub sample_rows: usize,
+    /// Fail the load when more than this percentage of rows has an issue
+    pub max_bad_rows_pct: Option<f64>,
+    /// Where to also write the report as JSON
+    pub report_path: Option<PathBuf>,
+}
+
+impl Default for QualityOptions {
+    fn default() -> Self {
+        Self {
+            min_event_time: DEFAULT_MIN_EVENT_TIME.to_string(),
+            max_event_time: default_max_event_time(),
+            sample_rows: 5,
+            max_bad_rows_pct: None,
+            report_path: None,
+        }
+    }
+}
+
+/// A day past now in UTC, so producers with a slightly fast clock are not flagged
+pub fn default_max_event_time() -> String {
+    (chrono::Utc::now() + chrono::Duration::days(1)).format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// Normalize a `YYYY-MM-DD` or `YYYY-MM-DD HH:MM:SS` UTC event time for use in SQL
+pub fn parse_event_time(value: &str) -> Result<String> {
+    let parsed = chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S"))
+        .or_else(|_| chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d").map(|d| d.and_time(chrono::NaiveTime::MIN)))
+        .map_err(|_| anyhow::anyhow!("Invalid event time `{}`; expected YYYY-MM-DD or YYYY-MM-DD HH:MM:SS", value))?;
+    Ok(parsed.format("%Y-%m-%d %H:%M:%S").to_string())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnQuality {
+    pub name: String,
+    /// Missing or empty raw values
+    pub nulls: u64,
+    /// Non-empty raw values the schema type could not parse, which `TRY_CAST` turns into NULL
+    pub failed_casts: u64,
+    /// Timestamp column only: parsed event times outside the accepted range
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub out_of_range: Option<u64>,
+    /// Enum columns only: the most frequent values outside the enum, with their counts
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unknown_values: Vec<(String, u64)>,
+}
+
+/// One offending row as it was read from the input files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SampleRow {
+    /// Raw values in `QualityReport::sample_columns` order
+    pub values: Vec<Option<String>>,
+    pub issues: Vec<String>,
+}
+
+/// Data quality of the raw events, checked before they are cast and cached as Parquet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QualityReport {
+    pub source: String,
+    pub total_rows: u64,
+    /// Rows with a failed cast or a missing or out-of-range timestamp
+    pub bad_rows: u64,
+    pub min_event_time: String,
+    pub max_event_time: String,
+    pub columns: Vec<ColumnQuality>,
+    pub sample_columns: Vec<String>,
+    pub samples: Vec<SampleRow>,
+}
+
+/// Everything that makes a row bad, as (description, SQL predicate over the `checked` relation)
+fn issue_predicates(schema: &EventSchema, options: &QualityOptions) -> Vec<(String, String)> {
+    let mut issues = Vec::new();
+    for col in &schema.columns {
+        if let Some(predicate) = failed_cast_predicate(col) {
+            let description = match &col.column_type {
+                ColumnType::Enum { .. } => format!("{}: unknown value", col.name),
+                _ => format!("{}: not a valid {}", col.name, type_name(&col.column_type)),
+            };
+            issues.push((description, predicate));
+        }
+    }
+    let ts = &schema.timestamp_column;
+    issues.push((format!("{}: missing", ts), format!("NULLIF({}, '') IS NULL", ts)));
+    issues.push((format!("{}: out of range", ts), out_of_range_predicate(ts, "__typed_", options)));
+    issues
+}
+
+fn type_name(column_type: &ColumnType) -> &'static str {
+    match column_type {
+        ColumnType::Varchar => "varchar",
+        ColumnType::Integer => "integer",
+        ColumnType::Bigint => "bigint",
+        ColumnType::Double => "double",
+        ColumnType::Timestamp => "timestamp",
+        ColumnType::Enum { .. } => "enum",
+    }
+}
+
+/// Raw value present but lost by the cast; VARCHAR columns cannot fail
+fn failed_cast_predicate(col: &ColumnDef) -> Option<String> {
+    (col.column_type != ColumnType::Varchar)
+        .then(|| format!("(NULLIF({0}, '') IS NOT NULL AND __typed_{0} IS NULL)", col.name))
+}
+
+/// `<predicate> AS __failed_<col>` per column that can fail its cast, over the `checked` relation.
+/// The loader writes these flags into the Parquet cache, so `check_raw_events` can count from
+/// the cache instead of scanning and casting the raw files a second time.
+pub fn failed_cast_flags(schema: &EventSchema) -> Vec<String> {
+    schema
+        .columns
+        .iter()
+        .filter_map(|col| failed_cast_predicate(col).map(|predicate| format!("{} AS __failed_{}", predicate, col.name)))
+        .collect()
+}
+
+/// `prefix` is `__typed_` over the `checked` relation and empty over the typed cache. The
+/// bounds carry an explicit UTC offset; a naive literal would be read in the session time zone.
+fn out_of_range_predicate(ts: &str, prefix: &str, options: &QualityOptions) -> String {
+    format!(
+        "({0}{1} < '{2}+00' OR {0}{1} > '{3}+00')",
+        prefix, ts, options.min_event_time, options.max_event_time
+    )
+}
+
+/// The raw VARCHAR columns next to their typed values, so checks can compare the two
+pub fn checked_relation_sql(schema: &EventSchema) -> String {
+    let typed = schema
+        .columns
+        .iter()
+        .map(|c| format!("{} AS __typed_{}", schema.cast_expr(c), c.name))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("WITH checked AS (SELECT *, {} FROM raw_events)", typed)
+}
+
+/// Per-column counts from `cache`, the typed relation written with `failed_cast_flags`; the
+/// `raw_events` view (every schema column as VARCHAR) is only read again for unknown enum
+/// values and sample rows, when there is something to show
+pub fn check_raw_events(
+    con: &Connection,
+    schema: &EventSchema,
+    cache: &str,
+    source: &str,
+    options: &QualityOptions,
+) -> Result<QualityReport> {
+    let checked = checked_relation_sql(schema);
+    let issues = issue_predicates(schema, options);
+    let any_issue = issues.iter().map(|(_, p)| p.as_str()).collect::<Vec<_>>().join(" OR ");
+
+    // Per column: nulls, failed casts, and for the timestamp out-of-range values. A typed NULL
+    // is either a missing raw value or a failed cast; VARCHAR columns keep empty strings.
+    let ts = &schema.timestamp_column;
+    let mut cached_issue = vec![format!("{} IS NULL", ts), out_of_range_predicate(ts, "", options)];
+    let mut column_counts = Vec::new();
+    for col in &schema.columns {
+        if col.column_type == ColumnType::Varchar {
+            column_counts.push(format!("COUNT(*) FILTER (WHERE NULLIF({}, '') IS NULL)", col.name));
+            column_counts.push("0".to_string());
+        } else {
+            column_counts.push(format!("COUNT(*) FILTER (WHERE {0} IS NULL AND NOT __failed_{0})", col.name));
+            column_counts.push(format!("COUNT(*) FILTER (WHERE __failed_{})", col.name));
+            cached_issue.push(format!("__failed_{}", col.name));
+        }
+        if &col.name == ts {
+            column_counts.push(format!("COUNT(*) FILTER (WHERE {})", out_of_range_predicate(ts, "", options)));
+        }
+    }
+    let mut counts = vec!["COUNT(*)".to_string(), format!("COUNT(*) FILTER (WHERE {})", cached_issue.join(" OR "))];
+    counts.extend(column_counts);
+    let values: Vec<u64> = con.query_row(
+        &format!("SELECT {} FROM {}", counts.join(", "), cache),
+        [],
+        |row| (0..counts.len()).map(|i| row.get::<_, i64>(i).map(|v| v as u64)).collect(),
+    )?;
+
+    let mut values = values.into_iter();
+    let mut next = || values.next().unwrap_or(0);
+    let total_rows = next();
+    let bad_rows = next();
+    let mut columns = Vec::new();
+    for col in &schema.columns {
+        let nulls = next();
+        let failed_casts = next();
+        let out_of_range = (col.name == schema.timestamp_column).then(&mut next);
+        columns.push(ColumnQuality {
+            name: col.name.clone(),
+            nulls,
+            failed_casts,
+            out_of_range,
+            unknown_values: Vec::new(),
+        });
+    }
+
+    for (col, quality) in schema.columns.iter().zip(columns.iter_mut()) {
+        if !matches!(col.column_type, ColumnType::Enum { .. }) || quality.failed_casts == 0 {
+            continue;
+        }
+        let sql = format!(
+            "{} SELECT {}, COUNT(*) FROM checked WHERE {} GROUP BY 1 ORDER BY 2 DESC, 1 LIMIT {}",
+            checked,
+            col.name,
+            failed_cast_predicate(col).unwrap_or_default(),
+            TOP_UNKNOWN_VALUES
+        );
+        let mut stmt = con.prepare(&sql)?;
+        quality.unknown_values = stmt
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as u64)))?
+            .collect::<Result<_, _>>()?;
+    }
+
+    let sample_columns: Vec<String> = schema.columns.iter().map(|c| c.name.clone()).collect();
+    let mut samples = Vec::new();
+    if bad_rows > 0 && options.sample_rows > 0 {
+        let labels = issues
+            .iter()
+            .map(|(description, predicate)| format!("CASE WHEN {} THEN '{}' END", predicate, description))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let sql = format!(
+            "{} SELECT {}, concat_ws('; ', {}) FROM checked WHERE {} LIMIT {}",
+            checked,
+            sample_columns.join(", "),
+            labels,
+            any_issue,
+            options.sample_rows
+        );
+        let mut stmt = con.prepare(&sql)?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let values = (0..sample_columns.len())
+                .map(|i| row.get::<_, Option<String>>(i))
+                .collect::<Result<Vec<_>, _>>()?;
+            let issues: String = row.get(sample_columns.len())?;
+            samples.push(SampleRow {
+                values,
+                issues: issues.split("; ").map(|s| s.to_string()).collect(),
+            });
+        }
+    }
+
+    Ok(QualityReport {
+        source: source.to_string(),
+        total_rows,
+        bad_rows,
+        min_event_time: options.min_event_time.clone(),
+        max_event_time: options.max_event_time.clone(),
+        columns,
+        sample_columns,
+        samples,
+    })
+}
+
+impl QualityReport {
+    pub fn bad_rows_pct(&self) -> f64 {
+        if self.total_rows == 0 {
+            0.0
+        } else {
+            self.bad_rows as f64 / self.total_rows as f64 * 100.0
+        }
+    }
+
+    /// Fail when the share of bad rows is above `--max-bad-rows-pct`
+    pub fn enforce(&self, options: &QualityOptions) -> Result<()> {
+        if let Some(max_pct) = options.max_bad_rows_pct
+            && self.bad_rows_pct() > max_pct
+        {
+            anyhow::bail!(
+                "{}\n{:.2}% of rows failed data-quality checks, above --max-bad-rows-pct {}",
+                self,
+                self.bad_rows_pct(),
+                max_pct
+            );
+        }
+        Ok(())
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for QualityReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Data quality of {}: {} rows, {} with issues ({:.2}%)",
+            self.source,
+            self.total_rows,
+            self.bad_rows,
+            self.bad_rows_pct()
+        )?;
+        for col in &self.columns {
+            write!(f, "\n    {}: {} null, {} failed casts", col.name, col.nulls, col.failed_casts)?;
+            if let Some(out_of_range) = col.out_of_range {
+                write!(
+                    f,
+                    ", {} outside {} .. {} UTC",
+                    out_of_range, self.min_event_time, self.max_event_time
+                )?;
+            }
+            if !col.unknown_values.is_empty() {
+                let unknown: Vec<String> = col.unknown_values.iter().map(|(v, n)| format!("{} ({})", v, n)).collect();
+                write!(f, "; unknown values: {}", unknown.join(", "))?;
+            }
+        }
+        for sample in &self.samples {
+            let row: Vec<String> = self
+                .sample_columns
+                .iter()
+                .zip(&sample.values)
+                .map(|(name, value)| format!("{}={}", name, value.as_deref().unwrap_or("")))
+                .collect();
+            write!(f, "\n    sample [{}]: {}", sample.issues.join("; "), row.join(", "))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// Cache file written next to the database so the probe runs once per disk
+const CACHE_FILE: &str = ".calhacks_disk_probe.json";
+const PROBE_FILE: &str = ".calhacks_disk_probe.tmp";
+
+/// Sequential read size; large enough to get past readahead, small enough to stay under a second on NVMe
+const PROBE_BYTES: usize = 64 * 1024 * 1024;
+const CHUNK_BYTES: usize = 1024 * 1024;
+/// Random 4 KiB reads used to estimate access latency
+const LATENCY_READS: usize = 64;
+const LATENCY_READ_BYTES: usize = 4096;
+
+/// Read throughput and latency of the disk holding the database
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiskProbe {
+    pub dir: PathBuf,
+    pub read_mb_s: f64,
+    pub latency_ms: f64,
+    pub probed_at: String,
+}
+
+/// Load the cached probe for `dir`, or measure the disk and cache the result
+pub fn probe_cached(dir: &Path, force: bool) -> Result<DiskProbe> {
+    let cache_path = dir.join(CACHE_FILE);
+    if !force
+        && let Ok(content) = std::fs::read_to_string(&cache_path)
+        && let Ok(cached) = serde_json::from_str::<DiskProbe>(&content)
+    {
+        return Ok(cached);
+    }
+
+    // Runs before any progress bar exists, so it is announced on stdout like the startup output
+    println!(
+        "Probing disk read throughput: writing a {} MB temporary file in {} (result cached in {}; skip with --no-disk-probe)",
+        PROBE_BYTES / 1_048_576,
+        dir.display(),
+        CACHE_FILE
+    );
+    let probe = run_probe(dir)?;
+    std::fs::write(&cache_path, serde_json::to_string_pretty(&probe)?)?;
+    Ok(probe)
+}
+
+fn run_probe(dir: &Path) -> Result<DiskProbe> {
+    let path = dir.join(PROBE_FILE);
+    let result = measure(&path);
+    let _ = std::fs::remove_file(&path);
+    let (read_mb_s, latency_ms) = result?;
+
+    Ok(DiskProbe {
+        dir: dir.to_path_buf(),
+        read_mb_s,
+        latency_ms,
+        probed_at: chrono::Local::now().to_rfc3339(),
+    })
+}
+
+fn measure(path: &Path) -> Result<(f64, f64)> {
+    // Incompressible-ish data so compressing filesystems do not flatter the result
+    let mut chunk = vec![0u8; CHUNK_BYTES];
+    let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+    for byte in chunk.iter_mut() {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        *byte = state as u8;
+    }
+
+    let mut file = File::create(path)?;
+    for _ in 0..PROBE_BYTES / CHUNK_BYTES {
+        file.write_all(&chunk)?;
+    }
+    file.sync_all()?;
+    drop(file);
+
+    // Sequential throughput
+    let mut file = File::open(path)?;
+    evict_from_page_cache(&file);
+    let start = Instant::now();
+    let mut read = 0;
+    while read < PROBE_BYTES {
+        let n = file.read(&mut chunk)?;
+        if n == 0 {
+            break;
+        }
+        read += n;
+    }
+    let read_mb_s = read as f64 / 1_048_576.0 / start.elapsed().as_secs_f64().max(f64::EPSILON);
+
+    // Random access latency, spread over the file with a fixed stride so no two reads share a page
+    evict_from_page_cache(&file);
+    let mut block = vec![0u8; LATENCY_READ_BYTES];
+    let stride = (PROBE_BYTES / LATENCY_READS) as u64;
+    let start = Instant::now();
+    for i in 0..LATENCY_READS as u64 {
+        // Visit blocks out of order to defeat readahead
+        let offset = (i * 37 % LATENCY_READS as u64) * stride;
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut block)?;
+    }
+    let latency_ms = start.elapsed().as_secs_f64() * 1000.0 / LATENCY_READS as f64;
+
+    Ok((read_mb_s, latency_ms))
+}
+
+/// Drop the file's pages from the OS page cache so reads hit the device.
+/// Best effort: other platforms measure cached reads, which overestimates the disk.
+fn evict_from_page_cache(file: &File) {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::io::AsRawFd;
+        unsafe {
+            libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED);
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = file;
+}
@@ -0,0 +1,408 @@
+use anyhow::Result;
+use duckdb::Connection;
+use indicatif::ProgressBar;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::mv::MaterializedView;
+use crate::planner::Planner;
+use crate::query_executor::write_single_result;
+use crate::query_handler::assemble_sql;
+use crate::result_checker::{ToleranceConfig, compare_csv_files};
+use crate::result_writer::OutputFormat;
+use crate::rng::Rng;
+use crate::schema::{ColumnType, DERIVED_TIME_COLUMNS, EventSchema};
+
+/// Rows of `events` sampled for predicate values
+const SAMPLE_ROWS: usize = 20_000;
+
+const AGGREGATES: &[&str] = &["SUM", "AVG", "COUNT", "MIN", "MAX"];
+
+/// Upper bound on query executions spent shrinking one failure
+const MAX_SHRINK_CHECKS: usize = 200;
+
+pub struct FuzzOptions<'a> {
+    pub iterations: usize,
+    pub seed: u64,
+    /// Where minimized failing queries are written, one JSON file each
+    pub failure_dir: &'a Path,
+    pub tolerances: &'a ToleranceConfig,
+}
+
+/// Outcome of running one query both ways
+enum Check {
+    /// MV plan and raw plan agree (or no MV was chosen)
+    Pass,
+    /// The raw plan itself failed, so the generated query was not valid
+    Invalid,
+    Fail(String),
+}
+
+/// Generate random queries, run each through the planner and through plain SQL on raw
+/// `events`, and compare the two results with the result checker. Failing queries are shrunk
+/// to a minimal reproduction and saved. Returns the number of failures.
+pub fn run_fuzzer(con: &Connection, mvs: &mut [MaterializedView], opts: &FuzzOptions, pb: &ProgressBar) -> Result<usize> {
+    let columns = FuzzColumns::sample(con, crate::schema::get())?;
+    if columns.dimensions.is_empty() {
+        anyhow::bail!("The event schema has no column a fuzzed query could group or filter by");
+    }
+    let planner = Planner::new(con);
+    let scratch = opts.failure_dir.join(".scratch");
+    let mut rng = Rng::new(opts.seed);
+
+    let mut failures = 0;
+    let mut routed = 0;
+    let mut invalid = 0;
+
+    for iteration in 0..opts.iterations {
+        let query = generate_query(&mut rng, &columns);
+        let mv_sql = planner.translate_query(&query, mvs, false)?;
+        if mv_sql != assemble_sql(&query) {
+            routed += 1;
+        }
+
+        match check_query(con, &planner, mvs, &query, opts.tolerances, &scratch)? {
+            Check::Pass => {}
+            Check::Invalid => invalid += 1,
+            Check::Fail(reason) => {
+                failures += 1;
+                pb.println(format!("Iteration {}: {}", iteration, reason));
+                let minimal = shrink(con, &planner, mvs, &query, opts.tolerances, &scratch)?;
+                let reason = match check_query(con, &planner, mvs, &minimal, opts.tolerances, &scratch)? {
+                    Check::Fail(reason) => reason,
+                    _ => reason,
+                };
+
+                std::fs::create_dir_all(opts.failure_dir)?;
+                let path = opts.failure_dir.join(format!("failure_{}_{}.json", opts.seed, iteration));
+                let report = json!({
+                    "seed": opts.seed,
+                    "iteration": iteration,
+                    "reason": reason,
+                    "minimal": minimal,
+                    "mv_sql": planner.translate_query(&minimal, mvs, false)?,
+                    "raw_sql": assemble_sql(&minimal),
+                    "original": query,
+                });
+                std::fs::write(&path, serde_json::to_string_pretty(&report)?)?;
+                pb.println(format!("    minimized to {} -> {}", minimal, path.display()));
+            }
+        }
+        pb.inc(1);
+    }
+
+    let _ = std::fs::remove_dir_all(&scratch);
+    pb.println(format!(
+        "Fuzzed {} queries (seed {}): {} routed to MVs, {} invalid, {} failures",
+        opts.iterations, opts.seed, routed, invalid, failures
+    ));
+    // Nothing was compared, e.g. because the generated columns do not exist in `events`
+    if opts.iterations > 0 && invalid == opts.iterations {
+        anyhow::bail!("Every fuzzed query failed on raw events; no plan was checked");
+    }
+    Ok(failures)
+}
+
+/// What generated queries are built from, derived from the event schema
+struct FuzzColumns {
+    /// Columns a query may group or filter by
+    dimensions: Vec<String>,
+    /// Columns aggregated in the select list
+    metrics: Vec<String>,
+    /// Distinct values per dimension from a sample of `events`, so predicates hit real data
+    domains: HashMap<String, Vec<String>>,
+}
+
+impl FuzzColumns {
+    /// Derived time, enum, varchar and integer columns are dimensions, other numeric columns
+    /// metrics. A dimension whose sampled values are mostly distinct (an auction id, say) would
+    /// make group-bys as large as the table, so it is left out.
+    fn sample(con: &Connection, schema: &EventSchema) -> Result<Self> {
+        let mut candidates: Vec<String> = DERIVED_TIME_COLUMNS.iter().map(|(name, _)| name.to_string()).collect();
+        candidates.extend(
+            schema
+                .columns
+                .iter()
+                .filter(|c| c.name != schema.timestamp_column)
+                .filter(|c| {
+                    matches!(c.column_type, ColumnType::Enum { .. } | ColumnType::Varchar | ColumnType::Integer)
+                })
+                .map(|c| c.name.clone()),
+        );
+
+        con.execute(
+            &format!(
+                "CREATE OR REPLACE TEMP TABLE fuzz_sample AS SELECT {} FROM events USING SAMPLE {} ROWS",
+                candidates.join(", "),
+                SAMPLE_ROWS
+            ),
+            [],
+        )?;
+        let sampled: usize = con.query_row("SELECT COUNT(*) FROM fuzz_sample", [], |row| row.get(0))?;
+
+        let mut dimensions = Vec::new();
+        let mut domains = HashMap::new();
+        for dim in candidates {
+            let distinct: usize = con.query_row(&format!("SELECT COUNT(DISTINCT {dim}) FROM fuzz_sample"), [], |row| row.get(0))?;
+            if sampled > 0 && distinct * 2 > sampled {
+                continue;
+            }
+            let mut stmt = con.prepare(&format!(
+                "SELECT DISTINCT CAST({dim} AS VARCHAR) AS v FROM fuzz_sample WHERE {dim} IS NOT NULL ORDER BY v LIMIT 500"
+            ))?;
+            let values: Vec<String> = stmt.query_map([], |row| row.get(0))?.collect::<Result<_, _>>()?;
+            domains.insert(dim.clone(), values);
+            dimensions.push(dim);
+        }
+        con.execute("DROP TABLE IF EXISTS fuzz_sample", [])?;
+
+        let metrics = schema
+            .columns
+            .iter()
+            .filter(|c| schema.is_numeric(&c.name) && !dimensions.contains(&c.name))
+            .map(|c| c.name.clone())
+            .collect();
+        Ok(Self {
+            dimensions,
+            metrics,
+            domains,
+        })
+    }
+}
+
+fn generate_query(rng: &mut Rng, columns: &FuzzColumns) -> Value {
+    let mut group_by: Vec<&str> = Vec::new();
+    for _ in 0..rng.below(4) {
+        let dim = rng.pick(&columns.dimensions).as_str();
+        if !group_by.contains(&dim) {
+            group_by.push(dim);
+        }
+    }
+
+    let mut select: Vec<Value> = group_by.iter().map(|d| json!(d)).collect();
+    let mut agg_names = Vec::new();
+    for _ in 0..1 + rng.below(3) {
+        let op = *rng.pick(AGGREGATES);
+        let (op, col) = if columns.metrics.is_empty() {
+            ("COUNT", "*")
+        } else if op == "COUNT" && rng.chance(0.5) {
+            (op, "*")
+        } else {
+            (op, rng.pick(&columns.metrics).as_str())
+        };
+        let name = format!("{}({})", op, col);
+        if !agg_names.contains(&name) {
+            select.push(json!({ op: col }));
+            agg_names.push(name);
+        }
+    }
+
+    let mut where_clause = Vec::new();
+    for _ in 0..rng.below(4) {
+        let dim = rng.pick(&columns.dimensions).as_str();
+        let Some(values) = columns.domains.get(dim).filter(|v| !v.is_empty()) else {
+            continue;
+        };
+        let value = rng.pick(values).clone();
+        let predicate = match rng.below(5) {
+            0 => json!({ "col": dim, "op": "eq", "val": value }),
+            1 => json!({ "col": dim, "op": "neq", "val": value }),
+            2 => {
+                let vals: Vec<String> = (0..1 + rng.below(3)).map(|_| rng.pick(values).clone()).collect();
+                json!({ "col": dim, "op": "in", "val": vals })
+            }
+            3 => {
+                let other = rng.pick(values).clone();
+                let (low, high) = if value <= other { (value, other) } else { (other, value) };
+                json!({ "col": dim, "op": "between", "val": [low, high] })
+            }
+            _ => {
+                let op = *rng.pick(&["lt", "lte", "gt", "gte"]);
+                json!({ "col": dim, "op": op, "val": value })
+            }
+        };
+        where_clause.push(predicate);
+    }
+
+    let mut query = json!({ "select": select, "from": "events" });
+    if !where_clause.is_empty() {
+        query["where"] = json!(where_clause);
+    }
+    if !group_by.is_empty() {
+        query["group_by"] = json!(group_by);
+    }
+
+    // Order by group-by columns and aggregates, and only limit ordered results
+    let orderable: Vec<String> = group_by.iter().map(|s| s.to_string()).chain(agg_names).collect();
+    if rng.chance(0.5) {
+        let mut order_by: Vec<Value> = Vec::new();
+        for _ in 0..1 + rng.below(2) {
+            let col = rng.pick(&orderable).clone();
+            if !order_by.iter().any(|o| o["col"] == col.as_str()) {
+                let dir = *rng.pick(&["asc", "desc"]);
+                order_by.push(json!({ "col": col, "dir": dir }));
+            }
+        }
+        query["order_by"] = json!(order_by);
+        if rng.chance(0.5) {
+            query["limit"] = json!(1 + rng.below(50));
+        }
+    }
+
+    query
+}
+
+fn check_query(
+    con: &Connection,
+    planner: &Planner,
+    mvs: &mut [MaterializedView],
+    query: &Value,
+    tolerances: &ToleranceConfig,
+    scratch: &Path,
+) -> Result<Check> {
+    let raw_sql = assemble_sql(query);
+    let mv_sql = planner.translate_query(query, mvs, false)?;
+    if mv_sql == raw_sql {
+        return Ok(Check::Pass);
+    }
+
+    let raw_dir = scratch.join("raw");
+    let mv_dir = scratch.join("mv");
+    if run_to_csv(con, &raw_sql, &raw_dir).is_err() {
+        return Ok(Check::Invalid);
+    }
+    if let Err(e) = run_to_csv(con, &mv_sql, &mv_dir) {
+        return Ok(Check::Fail(format!("MV plan failed: {}", e)));
+    }
+
+    let diff = compare_csv_files(&raw_dir.join("q1.csv"), &mv_dir.join("q1.csv"), Some(query), tolerances)?;
+    if diff.is_match() {
+        return Ok(Check::Pass);
+    }
+    Ok(Check::Fail(diff.summary()))
+}
+
+fn run_to_csv(con: &Connection, sql: &str, dir: &PathBuf) -> Result<()> {
+    let mut stmt = con.prepare(sql)?;
+    let rows = stmt.query([])?;
+    write_single_result(1, rows, dir, OutputFormat::Csv)
+}
+
+/// Simpler variants of a query: each drops or narrows exactly one part of it
+fn reductions(query: &Value) -> Vec<Value> {
+    let mut candidates = Vec::new();
+    let list = |key: &str| query.get(key).and_then(Value::as_array).cloned().unwrap_or_default();
+
+    if query.get("limit").is_some() {
+        let mut q = query.clone();
+        q.as_object_mut().unwrap().remove("limit");
+        candidates.push(q);
+    }
+
+    let order_by = list("order_by");
+    for i in 0..order_by.len() {
+        let mut q = query.clone();
+        let mut rest = order_by.clone();
+        rest.remove(i);
+        if rest.is_empty() {
+            q.as_object_mut().unwrap().remove("order_by");
+            q.as_object_mut().unwrap().remove("limit");
+        } else {
+            q["order_by"] = json!(rest);
+        }
+        candidates.push(q);
+    }
+
+    let where_clause = list("where");
+    for (i, predicate) in where_clause.iter().enumerate() {
+        let mut q = query.clone();
+        let mut rest = where_clause.clone();
+        rest.remove(i);
+        if rest.is_empty() {
+            q.as_object_mut().unwrap().remove("where");
+        } else {
+            q["where"] = json!(rest);
+        }
+        candidates.push(q);
+
+        if let Some(vals) = predicate.get("val").and_then(Value::as_array)
+            && predicate["op"] == "in"
+            && vals.len() > 1
+        {
+            let mut q = query.clone();
+            q["where"][i]["val"] = json!([vals[0]]);
+            candidates.push(q);
+        }
+    }
+
+    // Dropping a group-by column also drops it from the select list and ordering
+    let group_by = list("group_by");
+    for (i, col) in group_by.iter().enumerate() {
+        let mut q = query.clone();
+        let mut rest = group_by.clone();
+        rest.remove(i);
+        if rest.is_empty() {
+            q.as_object_mut().unwrap().remove("group_by");
+        } else {
+            q["group_by"] = json!(rest);
+        }
+        let select: Vec<Value> = list("select").into_iter().filter(|s| s != col).collect();
+        q["select"] = json!(select);
+        let order_by: Vec<Value> = order_by.iter().filter(|o| &o["col"] != col).cloned().collect();
+        if order_by.is_empty() {
+            q.as_object_mut().unwrap().remove("order_by");
+            q.as_object_mut().unwrap().remove("limit");
+        } else {
+            q["order_by"] = json!(order_by);
+        }
+        candidates.push(q);
+    }
+
+    // Drop aggregates while at least one remains, unless the ordering depends on them
+    let select = list("select");
+    let aggregates = select.iter().filter(|s| s.is_object()).count();
+    for (i, item) in select.iter().enumerate() {
+        let Some((op, col)) = item.as_object().and_then(|o| o.iter().next()) else {
+            continue;
+        };
+        let name = format!("{}({})", op, col.as_str().unwrap_or(""));
+        if aggregates < 2 || order_by.iter().any(|o| o["col"] == name.as_str()) {
+            continue;
+        }
+        let mut q = query.clone();
+        let mut rest = select.clone();
+        rest.remove(i);
+        q["select"] = json!(rest);
+        candidates.push(q);
+    }
+
+    candidates
+}
+
+/// Greedily apply reductions that keep the query failing until none does
+fn shrink(
+    con: &Connection,
+    planner: &Planner,
+    mvs: &mut [MaterializedView],
+    query: &Value,
+    tolerances: &ToleranceConfig,
+    scratch: &Path,
+) -> Result<Value> {
+    let mut current = query.clone();
+    let mut checks = 0;
+    'outer: loop {
+        for candidate in reductions(&current) {
+            if checks >= MAX_SHRINK_CHECKS {
+                break 'outer;
+            }
+            checks += 1;
+            if let Check::Fail(_) = check_query(con, planner, mvs, &candidate, tolerances, scratch)? {
+                current = candidate;
+                continue 'outer;
+            }
+        }
+        break;
+    }
+    Ok(current)
+}
@@ -1,47 +1,191 @@
+use anyhow::Result;
+use duckdb::Connection;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 use std::sync::OnceLock;
 
+use crate::{cgroup, disk_probe};
+
 pub struct HardwareInfo {
     pub num_threads: usize,
     pub available_memory_gb: f64,
+    /// Where `num_threads` came from
+    pub cpu_source: HardwareSource,
+    /// Where `available_memory_gb` came from
+    pub memory_source: HardwareSource,
+    /// Sequential read throughput of the database disk in MB/s, when known
+    pub disk_read_mb_s: Option<f64>,
+    /// Mean random 4 KiB read latency of the database disk, when probed
+    pub disk_latency_ms: Option<f64>,
+    pub disk_source: HardwareSource,
+}
+
+/// Origin of a detected hardware value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HardwareSource {
+    /// Host-wide values (/proc/meminfo, CPU count)
+    Host,
+    /// Container limits from cgroup v1 (cpu.cfs_quota_us, memory.limit_in_bytes)
+    CgroupV1,
+    /// Container limits from cgroup v2 (cpu.max, memory.max)
+    CgroupV2,
+    /// Nothing could be detected; a fixed default is used
+    Default,
+    /// Hardware profile file (--hardware-profile or CALHACKS_HARDWARE_PROFILE)
+    ProfileFile,
+    /// CALHACKS_THREADS / CALHACKS_MEMORY_GB / CALHACKS_DISK_READ_MB_S
+    Env,
+    /// --hw-threads / --hw-memory-gb / --hw-disk-read-mb-s
+    Cli,
+    /// Measured by reading a scratch file next to the database (cached per directory)
+    DiskProbe,
+}
+
+impl std::fmt::Display for HardwareSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let label = match self {
+            HardwareSource::Host => "host",
+            HardwareSource::CgroupV1 => "cgroup v1",
+            HardwareSource::CgroupV2 => "cgroup v2",
+            HardwareSource::Default => "default",
+            HardwareSource::ProfileFile => "profile file",
+            HardwareSource::Env => "env",
+            HardwareSource::Cli => "cli",
+            HardwareSource::DiskProbe => "disk probe",
+        };
+        f.write_str(label)
+    }
 }
 
 static HARDWARE_INFO: OnceLock<HardwareInfo> = OnceLock::new();
 
+/// Memory the host could hand out right now, from /proc/meminfo
+fn host_available_memory_gb() -> Option<f64> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    let content = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let available_kb: u64 = content
+        .lines()
+        .find_map(|line| line.strip_prefix("MemAvailable:"))?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()?;
+    Some(available_kb as f64 / 1_048_576.0) // KB to GB
+}
+
 impl HardwareInfo {
+    /// Detect threads and memory, preferring container (cgroup) limits over host values
+    /// whenever they are tighter, so pods are not sized as if they owned the whole node.
     pub fn detect() -> Self {
-        let num_threads = num_cpus::get();
-        
-        // Try to detect memory (fallback to reasonable defaults)
-        let available_memory_gb = if cfg!(target_os = "linux") {
-            // Try to read from /proc/meminfo
-            if let Ok(content) = std::fs::read_to_string("/proc/meminfo") {
-                let mut available_kb = 0;
-                
-                for line in content.lines() {
-                    if line.starts_with("MemAvailable:") {
-                        if let Some(kb_str) = line.split_whitespace().nth(1) {
-                            available_kb = kb_str.parse().unwrap_or(0);
-                        }
-                    }
-                }
-                
-                available_kb as f64 / 1_048_576.0 // KB to GB
-            } else {
-                16.0 // Fallback
-            }
-        } else {
-            16.0 // Fallback for non-Linux
+        let host_threads = num_cpus::get();
+        let (host_memory_gb, host_memory_source) = match host_available_memory_gb() {
+            Some(gb) => (gb, HardwareSource::Host),
+            None => (16.0, HardwareSource::Default), // Fallback
         };
-        
-        Self {
-            num_threads,
-            available_memory_gb,
+
+        let mut info = Self {
+            num_threads: host_threads,
+            available_memory_gb: host_memory_gb,
+            cpu_source: HardwareSource::Host,
+            memory_source: host_memory_source,
+            disk_read_mb_s: None,
+            disk_latency_ms: None,
+            disk_source: HardwareSource::Default,
+        };
+
+        let Some(limits) = cgroup::detect() else {
+            return info;
+        };
+
+        if let Some(quota) = limits.cpu_quota {
+            // A fractional quota still gets a thread; DuckDB cannot use half a core anyway
+            let quota_threads = (quota.ceil() as usize).max(1);
+            if quota_threads < info.num_threads {
+                info.num_threads = quota_threads;
+                info.cpu_source = limits.source;
+            }
+        }
+
+        if let Some(bytes) = limits.memory_available_bytes {
+            let limit_gb = bytes as f64 / 1_073_741_824.0;
+            if host_memory_source == HardwareSource::Default || limit_gb < info.available_memory_gb {
+                info.available_memory_gb = limit_gb;
+                info.memory_source = limits.source;
+            }
         }
+
+        info
     }
     
     pub fn get() -> &'static HardwareInfo {
         HARDWARE_INFO.get_or_init(|| Self::detect())
     }
+
+    /// Detect the hardware, then layer overrides on top: profile file, then environment,
+    /// then command line. Must run before the first `get()` for the overrides to take effect.
+    ///
+    /// Unless an override supplies the disk throughput, the disk under `disk_probe_dir` is
+    /// probed (or its cached probe loaded; `reprobe` forces a fresh measurement).
+    pub fn init(
+        cli: &HardwareProfile,
+        profile_path: Option<&Path>,
+        disk_probe_dir: Option<&Path>,
+        reprobe: bool,
+    ) -> Result<&'static HardwareInfo> {
+        let mut info = Self::detect();
+
+        let env_profile_path = std::env::var_os("CALHACKS_HARDWARE_PROFILE").map(PathBuf::from);
+        if let Some(path) = profile_path.or(env_profile_path.as_deref()) {
+            info.apply_profile(&HardwareProfile::load(path)?, HardwareSource::ProfileFile);
+        }
+        info.apply_profile(&HardwareProfile::from_env()?, HardwareSource::Env);
+        info.apply_profile(cli, HardwareSource::Cli);
+
+        if info.disk_read_mb_s.is_none()
+            && let Some(dir) = disk_probe_dir
+        {
+            match disk_probe::probe_cached(dir, reprobe) {
+                Ok(probe) => {
+                    info.disk_read_mb_s = Some(probe.read_mb_s);
+                    info.disk_latency_ms = Some(probe.latency_ms);
+                    info.disk_source = HardwareSource::DiskProbe;
+                }
+                Err(e) => println!("Warning: disk probe failed in {}: {}", dir.display(), e),
+            }
+        }
+
+        if HARDWARE_INFO.set(info).is_err() {
+            anyhow::bail!("Hardware info was already initialized");
+        }
+        Ok(Self::get())
+    }
+
+    fn apply_profile(&mut self, profile: &HardwareProfile, source: HardwareSource) {
+        if let Some(threads) = profile.threads {
+            self.num_threads = threads.max(1);
+            self.cpu_source = source;
+        }
+        if let Some(memory_gb) = profile.memory_gb {
+            self.available_memory_gb = memory_gb;
+            self.memory_source = source;
+        }
+        if let Some(disk_read_mb_s) = profile.disk_read_mb_s {
+            self.disk_read_mb_s = Some(disk_read_mb_s);
+            self.disk_source = source;
+        }
+    }
+
+    /// The active values as a profile, e.g. to capture a production box and replay it elsewhere
+    pub fn profile(&self) -> HardwareProfile {
+        HardwareProfile {
+            threads: Some(self.num_threads),
+            memory_gb: Some(self.available_memory_gb),
+            disk_read_mb_s: self.disk_read_mb_s,
+        }
+    }
     
     /// Calculate optimal row group size for Parquet
     /// Winner's approach: aim for ~20 row groups per thread
@@ -80,9 +224,208 @@ impl HardwareInfo {
         
         (scan_weight, rollup_weight)
     }
+
+    /// Scan weight for an MV of roughly `mv_bytes`. Whatever does not fit in available memory
+    /// is re-read from disk on every scan, so that fraction is scaled by how much slower the
+    /// disk is than an in-memory scan. Unknown disk speed or MVs that fit leave it unchanged.
+    pub fn scan_weight_for(&self, scan_weight: f64, mv_bytes: f64) -> f64 {
+        let Some(read_mb_s) = self.disk_read_mb_s else {
+            return scan_weight;
+        };
+        let memory_bytes = self.available_memory_gb * 1_073_741_824.0;
+        if mv_bytes <= memory_bytes || mv_bytes <= 0.0 {
+            return scan_weight;
+        }
+
+        // DuckDB reads 256 KiB blocks; per-block latency lowers the effective throughput
+        let block_mb = 0.25;
+        let latency_s = self.disk_latency_ms.unwrap_or(0.0) / 1000.0;
+        let effective_mb_s = block_mb / (latency_s + block_mb / read_mb_s.max(1.0));
+
+        let spilled_fraction = (mv_bytes - memory_bytes) / mv_bytes;
+        let disk_slowdown = (IN_MEMORY_SCAN_MB_S / effective_mb_s).max(1.0);
+        scan_weight * (1.0 - spilled_fraction + spilled_fraction * disk_slowdown)
+    }
 }
 
+/// Rough single-query scan throughput of DuckDB over data already in memory
+const IN_MEMORY_SCAN_MB_S: f64 = 5000.0;
+
 pub fn get_hardware_info() -> &'static HardwareInfo {
     HardwareInfo::get()
 }
 
+impl std::fmt::Display for HardwareInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (scan_weight, rollup_weight) = self.cost_weights();
+        write!(
+            f,
+            "{} threads ({}), {:.1} GB memory ({}), disk ",
+            self.num_threads, self.cpu_source, self.available_memory_gb, self.memory_source
+        )?;
+        match (self.disk_read_mb_s, self.disk_latency_ms) {
+            (Some(mb_s), Some(latency_ms)) => write!(f, "{:.0} MB/s, {:.2} ms latency ({})", mb_s, latency_ms, self.disk_source)?,
+            (Some(mb_s), None) => write!(f, "{:.0} MB/s ({})", mb_s, self.disk_source)?,
+            (None, _) => write!(f, "unknown")?,
+        }
+        write!(f, "; scan weight {:.2}, rollup weight {:.2}", scan_weight, rollup_weight)
+    }
+}
+
+/// Hardware values to simulate instead of (or on top of) what was detected.
+/// Any field left out keeps the detected value.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HardwareProfile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threads: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_gb: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disk_read_mb_s: Option<f64>,
+}
+
+impl HardwareProfile {
+    /// JSON file such as `{"threads": 10, "memory_gb": 18, "disk_read_mb_s": 2000}`
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read hardware profile {}: {}", path.display(), e))?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn from_env() -> Result<Self> {
+        fn var<T: std::str::FromStr>(name: &str) -> Result<Option<T>> {
+            match std::env::var(name) {
+                Ok(value) => value
+                    .trim()
+                    .parse()
+                    .map(Some)
+                    .map_err(|_| anyhow::anyhow!("Invalid value for {}: {}", name, value)),
+                Err(_) => Ok(None),
+            }
+        }
+
+        Ok(Self {
+            threads: var("CALHACKS_THREADS")?,
+            memory_gb: var("CALHACKS_MEMORY_GB")?,
+            disk_read_mb_s: var("CALHACKS_DISK_READ_MB_S")?,
+        })
+    }
+}
+
+
+/// DuckDB resource settings for one phase of the run (preprocessing or queries)
+#[derive(Debug, Clone)]
+pub struct DuckDbSettings {
+    pub threads: usize,
+    pub memory_limit_gb: f64,
+    /// None keeps DuckDB's default (`<database>.tmp` next to the database file)
+    pub temp_directory: Option<PathBuf>,
+    pub preserve_insertion_order: bool,
+}
+
+/// Settings as DuckDB reports them after being applied
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EffectiveDuckDbSettings {
+    pub threads: String,
+    pub memory_limit: String,
+    pub temp_directory: String,
+    pub preserve_insertion_order: String,
+}
+
+impl HardwareInfo {
+    /// Preprocessing builds MVs and Parquet from the full dataset: use every thread, leave
+    /// headroom for the OS, and drop insertion order so large CTAS/COPY can stream in parallel.
+    pub fn preprocess_settings(&self) -> DuckDbSettings {
+        DuckDbSettings {
+            threads: self.num_threads,
+            memory_limit_gb: self.available_memory_gb * 0.8,
+            temp_directory: None,
+            preserve_insertion_order: false,
+        }
+    }
+
+    /// Queries read small MVs; keep insertion order so unordered results stay stable across runs.
+    pub fn query_settings(&self) -> DuckDbSettings {
+        DuckDbSettings {
+            threads: self.num_threads,
+            memory_limit_gb: self.available_memory_gb * 0.8,
+            temp_directory: None,
+            preserve_insertion_order: true,
+        }
+    }
+}
+
+impl DuckDbSettings {
+    /// Replace derived values with whatever was given on the command line
+    pub fn with_overrides(
+        mut self,
+        threads: Option<usize>,
+        memory_limit_gb: Option<f64>,
+        temp_directory: Option<&PathBuf>,
+        preserve_insertion_order: Option<bool>,
+    ) -> Self {
+        if let Some(threads) = threads {
+            self.threads = threads;
+        }
+        if let Some(memory_limit_gb) = memory_limit_gb {
+            self.memory_limit_gb = memory_limit_gb;
+        }
+        if let Some(temp_directory) = temp_directory {
+            self.temp_directory = Some(temp_directory.clone());
+        }
+        if let Some(preserve_insertion_order) = preserve_insertion_order {
+            self.preserve_insertion_order = preserve_insertion_order;
+        }
+        self
+    }
+
+    pub fn apply(&self, con: &Connection) -> Result<()> {
+        con.execute(&format!("SET threads = {}", self.threads.max(1)), [])?;
+        // MiB granularity; DuckDB needs at least a little memory to start up
+        let memory_limit_mib = (self.memory_limit_gb * 1024.0).max(256.0) as u64;
+        con.execute(&format!("SET memory_limit = '{}MiB'", memory_limit_mib), [])?;
+        if let Some(temp_directory) = &self.temp_directory {
+            std::fs::create_dir_all(temp_directory)?;
+            con.execute(
+                &format!("SET temp_directory = '{}'", temp_directory.to_string_lossy().replace('\'', "''")),
+                [],
+            )?;
+        }
+        con.execute(&format!("SET preserve_insertion_order = {}", self.preserve_insertion_order), [])?;
+        Ok(())
+    }
+}
+
+impl EffectiveDuckDbSettings {
+    pub fn read(con: &Connection) -> Result<Self> {
+        let settings = con.query_row(
+            "SELECT current_setting('threads')::VARCHAR, current_setting('memory_limit')::VARCHAR, \
+             current_setting('temp_directory')::VARCHAR, current_setting('preserve_insertion_order')::VARCHAR",
+            [],
+            |row| {
+                Ok(Self {
+                    threads: row.get(0)?,
+                    memory_limit: row.get(1)?,
+                    temp_directory: row.get(2)?,
+                    preserve_insertion_order: row.get(3)?,
+                })
+            },
+        )?;
+        Ok(settings)
+    }
+}
+
+impl std::fmt::Display for EffectiveDuckDbSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "threads={}, memory_limit={}, temp_directory={}, preserve_insertion_order={}",
+            self.threads, self.memory_limit, self.temp_directory, self.preserve_insertion_order
+        )
+    }
+}
@@ -0,0 +1,23 @@
+//! Library side of the crate: everything the `calhacks` binary drives, exposed so the
+//! integration tests under `tests/` can build a database and plan queries directly.
+
+pub mod data_loader;
+pub mod data_quality;
+pub mod preprocessor;
+pub mod query_executor;
+pub mod query_handler;
+pub mod result_checker;
+pub mod mv;
+pub mod planner;
+pub mod hardware;
+pub mod schema;
+pub mod cgroup;
+pub mod disk_probe;
+pub mod profile_summary;
+pub mod rng;
+pub mod fuzzer;
+pub mod data_generator;
+pub mod parallel_executor;
+pub mod result_writer;
+pub mod benchmark;
+pub mod watchdog;
@@ -16,21 +16,30 @@ fn format_duration_ms_ns(duration: Duration) -> String {
     format!("{:.2}ms", total_ms)
 }
 
-mod data_loader;
-mod preprocessor;
-mod query_executor;
-mod query_handler;
-mod result_checker;
-mod mv;
-mod planner;
-mod hardware;
-
-use data_loader::load_data;
-use preprocessor::{create_materialized_views, compute_mv_stats, warmup_cache, create_indexes, create_type_partitioned_materialized_views, load_all_mvs_from_db};
-use query_executor::{prepare_query, write_single_result_to_csv, explain_query};
-use query_handler::parse_queries_from_file;
-use result_checker::compare_results;
-use planner::Planner;
+fn format_ms(ms: f64) -> String {
+    format!("{:.2}ms", ms)
+}
+
+use calhacks::data_loader::{InputFormat, load_data};
+use calhacks::data_quality::{DEFAULT_MIN_EVENT_TIME, QualityOptions, default_max_event_time, parse_event_time};
+use calhacks::preprocessor::{create_materialized_views, compute_mv_stats, warmup_cache, create_indexes, create_type_partitioned_materialized_views, load_all_mvs_from_db};
+use calhacks::query_executor::{RunOptions, execute_timed, explain_query, generate_baseline, prepare_query, run_queries_cold};
+use calhacks::query_handler::{parse_queries_from_file, predicate_column_pairs};
+use calhacks::result_checker::{ToleranceConfig, compare_results};
+use calhacks::planner::Planner;
+use calhacks::mv::MIN_PARTITION_ROWS;
+use calhacks::parallel_executor::run_queries_parallel;
+use calhacks::result_writer::OutputFormat;
+use calhacks::benchmark::{
+    BenchMode, BenchmarkReport, EnvironmentInfo, PhaseReport, QueryReport, QueryStats, TimingMode, can_reach_significance,
+    compare_reports,
+};
+use calhacks::hardware::{EffectiveDuckDbSettings, HardwareInfo, HardwareProfile};
+use calhacks::watchdog::QueryWatchdog;
+use calhacks::profile_summary::WorkloadProfileSummary;
+use calhacks::fuzzer::{FuzzOptions, run_fuzzer};
+use calhacks::data_generator::{GeneratorConfig, generate_events};
+use calhacks::schema::EventSchema;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -38,18 +47,45 @@ struct Args {
     #[arg(long, value_name = "DIR", default_value = "data/data")]
     input_dir: PathBuf,
 
+    /// Format of the raw event files; detected from the files in --input-dir when omitted
+    #[arg(long, value_enum)]
+    input_format: Option<InputFormat>,
+
+    /// Glob of raw event files, relative to --input-dir (default depends on the format)
+    #[arg(long, value_name = "GLOB")]
+    input_glob: Option<String>,
+
+    /// JSON event schema describing the raw columns (default: the ad events schema)
+    #[arg(long, value_name = "FILE")]
+    schema: Option<PathBuf>,
+
+    /// Event times (UTC) before this count as out of range in the data-quality report
+    #[arg(long, value_name = "TS", default_value = DEFAULT_MIN_EVENT_TIME)]
+    min_event_time: String,
+
+    /// Event times (UTC) after this count as out of range (default: a day from now)
+    #[arg(long, value_name = "TS")]
+    max_event_time: Option<String>,
+
+    /// Offending raw rows shown in the data-quality report
+    #[arg(long, value_name = "N", default_value_t = 5)]
+    quality_samples: usize,
+
+    /// Fail preprocessing when more than PCT percent of raw rows have data-quality issues
+    #[arg(long, value_name = "PCT")]
+    max_bad_rows_pct: Option<f64>,
+
+    /// Also write the data-quality report as JSON to FILE
+    #[arg(long, value_name = "FILE")]
+    quality_report: Option<PathBuf>,
+
     #[arg(long)]
     run: bool,
 
     #[arg(long, value_name = "DIR")]
     output_dir: Option<PathBuf>,
 
-    #[arg(
-        long,
-        value_name = "FILE",
-        default_value = "queries.json",
-        requires = "run"
-    )]
+    #[arg(long, value_name = "FILE", default_value = "queries.json")]
     queries: PathBuf,
 
     #[arg(long, value_name = "FILE")]
@@ -58,11 +94,178 @@ struct Args {
     #[arg(long, value_name = "DIR")]
     baseline_dir: Option<PathBuf>,
 
+    /// Generate a synthetic events_part_*.csv dataset into DIR and use it as the input directory
+    #[arg(long, value_name = "DIR")]
+    generate_data: Option<PathBuf>,
+
+    /// Number of events for --generate-data
+    #[arg(long, default_value = "1000000")]
+    gen_rows: usize,
+
+    /// Number of CSV files for --generate-data
+    #[arg(long, default_value = "4")]
+    gen_parts: usize,
+
+    /// Days of timestamps for --generate-data
+    #[arg(long, default_value = "30")]
+    gen_days: usize,
+
+    /// Seed for --generate-data; the same seed and scale always produce the same files
+    #[arg(long, default_value = "42")]
+    gen_seed: u64,
+
+    /// Differential fuzzing: run N random queries through the planner and through raw SQL
+    /// on events, compare the results and save minimized failures
+    #[arg(long, value_name = "N")]
+    fuzz: Option<usize>,
+
+    /// Seed for --fuzz (default: derived from the clock and printed)
+    #[arg(long, value_name = "SEED")]
+    fuzz_seed: Option<u64>,
+
+    /// Where --fuzz writes minimized failing queries
+    #[arg(long, value_name = "DIR", default_value = "fuzz_failures")]
+    fuzz_dir: PathBuf,
+
+    /// Write reference CSVs to DIR by running every query's plain SQL on raw events (no MVs);
+    /// with --run, results are then compared against DIR unless --baseline-dir is given
+    #[arg(long, value_name = "DIR")]
+    generate_baseline: Option<PathBuf>,
+
     #[arg(long)]
     profile: bool,
 
+    /// Absolute tolerance for aggregate columns in --baseline-dir comparison
+    #[arg(long, value_name = "ABS")]
+    tolerance_abs: Option<f64>,
+
+    /// Relative tolerance for aggregate columns in --baseline-dir comparison
+    #[arg(long, value_name = "REL")]
+    tolerance_rel: Option<f64>,
+
+    /// Allowed distance in units in the last place for aggregate columns
+    #[arg(long, value_name = "N")]
+    tolerance_ulps: Option<u64>,
+
+    /// JSON file with per-column and per-aggregate tolerances (flags above override its default)
+    #[arg(long, value_name = "FILE")]
+    tolerance_config: Option<PathBuf>,
+
     #[arg(long, default_value = "1")]
     runs: usize,
+
+    /// Number of DuckDB connections to run queries on concurrently (1 = serial)
+    #[arg(long, default_value = "1")]
+    connections: usize,
+
+    /// Format for query result files; typed formats keep DuckDB's native column types
+    #[arg(long, value_enum, default_value = "csv")]
+    output_format: OutputFormat,
+
+    /// Where to write the JSON benchmark report (default: <output-dir>/benchmark.json)
+    #[arg(long, value_name = "FILE")]
+    report: Option<PathBuf>,
+
+    /// Cache state for timed runs: warm (default), cold (fresh database per run) or first-run
+    #[arg(long, value_enum, default_value = "warm")]
+    bench_mode: BenchMode,
+
+    /// In cold mode, drop the OS page cache before each run (Linux, requires root)
+    #[arg(long)]
+    drop_caches: bool,
+
+    /// What each timed run covers: execute (query only) or end-to-end (time to last serialized row)
+    #[arg(long, value_enum, default_value = "execute")]
+    timing: TimingMode,
+
+    /// With --timing end-to-end, serialize into a discard sink instead of the result files
+    #[arg(long)]
+    discard_output: bool,
+
+    /// Interrupt any single query execution that runs longer than this many seconds
+    #[arg(long, value_name = "SECS")]
+    query_timeout: Option<f64>,
+
+    /// Interrupt or skip remaining executions once the whole run exceeds this many seconds
+    #[arg(long, value_name = "SECS")]
+    global_timeout: Option<f64>,
+
+    /// Compare this run against a saved benchmark report and fail on regressions
+    #[arg(long, value_name = "FILE", requires = "run")]
+    compare_report: Option<PathBuf>,
+
+    /// Median slowdown (percent) beyond which a significant change counts as a regression
+    #[arg(long, default_value = "10.0")]
+    regression_threshold: f64,
+
+    /// Significance level for the Mann-Whitney U test used by --compare-report
+    #[arg(long, default_value = "0.05")]
+    significance: f64,
+
+    /// DuckDB threads while building the database (default: all hardware threads)
+    #[arg(long, value_name = "N")]
+    preprocess_threads: Option<usize>,
+
+    /// DuckDB memory limit in GB while building the database (default: 80% of available memory)
+    #[arg(long, value_name = "GB")]
+    preprocess_memory_limit: Option<f64>,
+
+    /// Keep insertion order while building the database (default: false)
+    #[arg(long, value_name = "BOOL")]
+    preprocess_preserve_insertion_order: Option<bool>,
+
+    /// Directory DuckDB spills to while building the database (default: --temp-directory)
+    #[arg(long, value_name = "DIR")]
+    preprocess_temp_directory: Option<PathBuf>,
+
+    /// DuckDB threads while running queries (default: all hardware threads)
+    #[arg(long, value_name = "N")]
+    query_threads: Option<usize>,
+
+    /// DuckDB memory limit in GB while running queries (default: 80% of available memory)
+    #[arg(long, value_name = "GB")]
+    query_memory_limit: Option<f64>,
+
+    /// Keep insertion order while running queries (default: true)
+    #[arg(long, value_name = "BOOL")]
+    query_preserve_insertion_order: Option<bool>,
+
+    /// Directory DuckDB spills to while running queries (default: --temp-directory)
+    #[arg(long, value_name = "DIR")]
+    query_temp_directory: Option<PathBuf>,
+
+    /// Simulate a machine with this many threads (overrides detection, CALHACKS_THREADS)
+    #[arg(long, value_name = "N")]
+    hw_threads: Option<usize>,
+
+    /// Simulate a machine with this much available memory in GB (CALHACKS_MEMORY_GB)
+    #[arg(long, value_name = "GB")]
+    hw_memory_gb: Option<f64>,
+
+    /// Simulate a disk with this sequential read throughput in MB/s (CALHACKS_DISK_READ_MB_S)
+    #[arg(long, value_name = "MB_S")]
+    hw_disk_read_mb_s: Option<f64>,
+
+    /// JSON hardware profile to simulate (CALHACKS_HARDWARE_PROFILE); CLI and env values win
+    #[arg(long, value_name = "FILE")]
+    hardware_profile: Option<PathBuf>,
+
+    /// Re-measure disk throughput instead of using the cached probe next to the database
+    #[arg(long)]
+    reprobe_disk: bool,
+
+    /// Skip the disk throughput probe (scan costs then ignore the disk)
+    #[arg(long)]
+    no_disk_probe: bool,
+
+    /// Write the active hardware profile to FILE, e.g. to replay a production box elsewhere
+    #[arg(long, value_name = "FILE")]
+    save_hardware_profile: Option<PathBuf>,
+
+    /// Directory DuckDB spills to when an operator exceeds the memory limit, in both phases
+    /// unless --preprocess-temp-directory or --query-temp-directory say otherwise (default: <db>.tmp)
+    #[arg(long, value_name = "DIR")]
+    temp_directory: Option<PathBuf>,
 }
 
 fn find_next_db_filename() -> Result<PathBuf> {
@@ -87,21 +290,149 @@ fn find_next_db_filename() -> Result<PathBuf> {
 fn main() -> Result<()> {
     let args = Args::parse();
 
-    if let Some(baseline_dir) = &args.baseline_dir {
-        if !args.run {
-            let Some(output_dir) = &args.output_dir else {
-                anyhow::bail!("--output-dir required when using --baseline-dir");
-            };
-            return compare_results(baseline_dir, output_dir);
+    let mut tolerances = match &args.tolerance_config {
+        Some(path) => ToleranceConfig::load(path)?,
+        None => ToleranceConfig::default(),
+    };
+    if let Some(abs) = args.tolerance_abs {
+        tolerances.default.abs = abs;
+    }
+    if let Some(rel) = args.tolerance_rel {
+        tolerances.default.rel = rel;
+    }
+    if let Some(ulps) = args.tolerance_ulps {
+        tolerances.default.ulps = ulps;
+    }
+
+    if let Some(baseline_dir) = &args.baseline_dir
+        && !args.run
+        && args.generate_baseline.is_none()
+    {
+        let Some(output_dir) = &args.output_dir else {
+            anyhow::bail!("--output-dir required when using --baseline-dir");
+        };
+        // Query definitions enable order-aware comparison when available
+        let queries = if args.queries.exists() {
+            parse_queries_from_file(&args.queries)?
+        } else {
+            println!("{} not found; comparing results without row order", args.queries.display());
+            Vec::new()
+        };
+        return compare_results(baseline_dir, output_dir, &queries, &tolerances);
+    }
+
+    // A reused database keeps the schema it was built with unless --schema overrides it
+    let recorded_schema = match &args.use_existing {
+        Some(path) if path.exists() => EventSchema::load_from_db(&Connection::open(path)?)?,
+        _ => None,
+    };
+    let schema_source = match (&args.schema, recorded_schema) {
+        (Some(path), recorded) => {
+            let schema = calhacks::schema::init(EventSchema::load(path)?)?;
+            if recorded.is_some_and(|recorded| recorded != *schema) {
+                println!("Warning: --schema differs from the schema the existing database was built with");
+            }
+            Some(path.display().to_string())
+        }
+        (None, Some(recorded)) => {
+            calhacks::schema::init(recorded)?;
+            Some("the existing database".to_string())
         }
+        (None, None) => None,
+    };
+    if let Some(source) = schema_source {
+        let schema = calhacks::schema::get();
+        println!(
+            "Event schema: {} columns from {} (timestamp {}, partitioned by {})",
+            schema.columns.len(),
+            source,
+            schema.timestamp_column,
+            schema.partition_column.as_deref().unwrap_or("nothing")
+        );
     }
 
+    let quality_options = QualityOptions {
+        min_event_time: parse_event_time(&args.min_event_time)?,
+        max_event_time: match &args.max_event_time {
+            Some(ts) => parse_event_time(ts)?,
+            None => default_max_event_time(),
+        },
+        sample_rows: args.quality_samples,
+        max_bad_rows_pct: args.max_bad_rows_pct,
+        report_path: args.quality_report.clone(),
+    };
+
+    let input_dir = match &args.generate_data {
+        Some(dir) => {
+            let config = GeneratorConfig::scaled(args.gen_rows, args.gen_parts, args.gen_seed, args.gen_days);
+            let gen_start = Instant::now();
+            let gen_pb = ProgressBar::new(config.parts as u64);
+            gen_pb.set_style(
+                ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} {msg}")
+                    .unwrap()
+                    .progress_chars("#>-")
+            );
+            gen_pb.set_message("Generating synthetic events...");
+            generate_events(dir, &config, &gen_pb)?;
+            gen_pb.finish_and_clear();
+            println!(
+                "Generated {} events in {} files under {} in {}",
+                config.rows,
+                config.parts,
+                dir.display(),
+                format_duration_seconds(gen_start.elapsed())
+            );
+            
+            // load_data reuses <input-dir>/../events.parquet when present; it would be stale now
+            let parquet_dir = dir.parent().unwrap_or(dir).join("events.parquet");
+            if parquet_dir.exists() {
+                println!("Removing stale {}", parquet_dir.display());
+                if parquet_dir.is_dir() {
+                    std::fs::remove_dir_all(&parquet_dir)?;
+                } else {
+                    std::fs::remove_file(&parquet_dir)?;
+                }
+            }
+            dir.clone()
+        }
+        None => args.input_dir.clone(),
+    };
+
     let db_path = if let Some(existing_path) = &args.use_existing {
         existing_path.clone()
     } else {
         find_next_db_filename()?
     };
     
+    let hw = HardwareInfo::init(
+        &HardwareProfile {
+            threads: args.hw_threads,
+            memory_gb: args.hw_memory_gb,
+            disk_read_mb_s: args.hw_disk_read_mb_s,
+        },
+        args.hardware_profile.as_deref(),
+        (!args.no_disk_probe).then(|| db_path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."))),
+        args.reprobe_disk,
+    )?;
+    println!("Hardware profile: {}", hw);
+    if let Some(path) = &args.save_hardware_profile {
+        hw.profile().write(path)?;
+        println!("Hardware profile written to {}", path.display());
+    }
+    let preprocess_settings = hw.preprocess_settings().with_overrides(
+        args.preprocess_threads,
+        args.preprocess_memory_limit,
+        args.preprocess_temp_directory.as_ref().or(args.temp_directory.as_ref()),
+        args.preprocess_preserve_insertion_order,
+    );
+    let query_settings = hw.query_settings().with_overrides(
+        args.query_threads,
+        args.query_memory_limit,
+        args.query_temp_directory.as_ref().or(args.temp_directory.as_ref()),
+        args.query_preserve_insertion_order,
+    );
+    let mut effective_preprocess_settings = None;
+
     // Part 1: Print DB file status
     if db_path.exists() && args.use_existing.is_some() {
         println!("Using existing database: {}", db_path.display());
@@ -124,20 +455,39 @@ fn main() -> Result<()> {
         }
         
         let file_con = Connection::open(&db_path)?;
+        preprocess_settings.apply(&file_con)?;
+        let effective = EffectiveDuckDbSettings::read(&file_con)?;
+        pb.println(format!("DuckDB settings (preprocessing): {}", effective));
+        effective_preprocess_settings = Some(effective);
+
         pb.set_message("Loading data...");
-        load_data(&file_con, &args.input_dir)?;
+        let loaded = load_data(&file_con, &input_dir, args.input_format, args.input_glob.as_deref(), &quality_options)?;
+        calhacks::schema::get().save_to_db(&file_con)?;
+        match &loaded.quality {
+            Some(report) => pb.println(report.to_string()),
+            None => {
+                let cache = loaded.parquet_dir.as_deref().unwrap_or(&input_dir).display().to_string();
+                if args.input_format.is_some() || args.input_glob.is_some() {
+                    pb.println(format!(
+                        "Ignoring --input-format/--input-glob: reusing the cached {} (delete it to reload the raw files)",
+                        cache
+                    ));
+                }
+                pb.println(format!("Reused the cached {}; data-quality checks only run on raw files", cache));
+            }
+        }
         pb.inc(1);
         
         pb.set_message("Creating materialized views...");
-        let mut mvs = create_materialized_views(&file_con)?;
+        let mut mvs = create_materialized_views(&file_con, &pb)?;
         pb.inc(1);
         
         pb.set_message("Computing MV statistics...");
-        compute_mv_stats(&file_con, &mut mvs)?;
+        compute_mv_stats(&file_con, &mut mvs, &[])?;
         pb.inc(1);
         
         pb.set_message("Creating type-partitioned MVs...");
-        let mut partitioned_mvs = create_type_partitioned_materialized_views(&file_con, &mvs)?;
+        let mut partitioned_mvs = create_type_partitioned_materialized_views(&file_con, &mvs, MIN_PARTITION_ROWS)?;
         pb.inc(1);
         
         let partitioned_count = partitioned_mvs.len();
@@ -147,7 +497,7 @@ fn main() -> Result<()> {
         
         pb.set_message("Computing partitioned MV statistics...");
         let total_mvs = mvs.len();
-        compute_mv_stats(&file_con, &mut mvs[total_mvs - partitioned_count..])?;
+        compute_mv_stats(&file_con, &mut mvs[total_mvs - partitioned_count..], &[])?;
         pb.inc(1);
         
         pb.set_message("Creating indexes...");
@@ -160,11 +510,106 @@ fn main() -> Result<()> {
     }
     
     let con = Connection::open(&db_path)?;
+    query_settings.apply(&con)?;
+    let effective_query_settings = EffectiveDuckDbSettings::read(&con)?;
+    println!("DuckDB settings (queries): {}", effective_query_settings);
+    
+    if let Some(golden_dir) = &args.generate_baseline {
+        let queries = parse_queries_from_file(&args.queries)?;
+        let golden_start = Instant::now();
+        
+        let golden_pb = ProgressBar::new(queries.len() as u64);
+        golden_pb.set_style(
+            ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} {msg}")
+                .unwrap()
+                .progress_chars("#>-")
+        );
+        golden_pb.set_message("Generating baseline from raw events...");
+        generate_baseline(&con, &queries, golden_dir, &golden_pb)?;
+        golden_pb.finish_and_clear();
+        
+        println!(
+            "Baseline for {} queries written to {} in {}",
+            queries.len(),
+            golden_dir.display(),
+            format_duration_seconds(golden_start.elapsed())
+        );
+        if !args.run {
+            return Ok(());
+        }
+    }
+    
+    if let Some(iterations) = args.fuzz {
+        let mut mvs = load_all_mvs_from_db(&con)?;
+        if mvs.is_empty() {
+            anyhow::bail!("--fuzz needs a database with materialized views");
+        }
+        compute_mv_stats(&con, &mut mvs, &[])?;
+        
+        let seed = args.fuzz_seed.unwrap_or_else(|| chrono::Local::now().timestamp_nanos_opt().unwrap_or(0) as u64);
+        println!("Fuzzing {} queries with seed {}", iterations, seed);
+        let fuzz_pb = ProgressBar::new(iterations as u64);
+        fuzz_pb.set_style(
+            ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} {msg}")
+                .unwrap()
+                .progress_chars("#>-")
+        );
+        let failures = run_fuzzer(
+            &con,
+            &mut mvs,
+            &FuzzOptions {
+                iterations,
+                seed,
+                failure_dir: &args.fuzz_dir,
+                tolerances: &tolerances,
+            },
+            &fuzz_pb,
+        )?;
+        fuzz_pb.finish_and_clear();
+        
+        if failures > 0 {
+            anyhow::bail!("{} fuzzed queries disagreed with raw SQL; see {}", failures, args.fuzz_dir.display());
+        }
+        if !args.run {
+            return Ok(());
+        }
+    }
+    
+    // A freshly generated baseline is the comparison target unless one was given explicitly
+    let baseline_dir = args.baseline_dir.as_ref().or(args.generate_baseline.as_ref());
     
     if args.run {
         let Some(output_dir) = &args.output_dir else {
             anyhow::bail!("--output-dir required with --run");
         };
+
+        if baseline_dir.is_some() && args.output_format != OutputFormat::Csv {
+            anyhow::bail!("--baseline-dir comparison requires --output-format csv");
+        }
+
+        if args.timing == TimingMode::EndToEnd && args.output_format != OutputFormat::Csv {
+            anyhow::bail!("--timing end-to-end requires --output-format csv");
+        }
+
+        if args.bench_mode != BenchMode::Warm && args.connections > 1 {
+            anyhow::bail!("--connections requires --bench-mode warm");
+        }
+        // First-run mode only ever times a single execution of each query
+        let runs = if args.bench_mode == BenchMode::FirstRun { 1 } else { args.runs };
+
+        // Load the saved report before this run writes its own: both default to <output-dir>/benchmark.json
+        let previous = args.compare_report.as_ref().map(|path| BenchmarkReport::load(path)).transpose()?;
+        if let Some(previous) = &previous
+            && !can_reach_significance(previous.runs, runs, args.significance)
+        {
+            anyhow::bail!(
+                "--compare-report cannot detect regressions with {} saved and {} current runs per query: \
+                 the Mann-Whitney U test never reaches --significance {} (use at least 4 runs on each side)",
+                previous.runs,
+                runs,
+                args.significance
+            );
+        }
         
         // Part 3: Query prep progress bar
         let prep_start = Instant::now();
@@ -186,97 +631,296 @@ fn main() -> Result<()> {
         
         if mvs.is_empty() {
             // Fallback: create base MVs if none exist
-            mvs = create_materialized_views(&con)?;
+            mvs = create_materialized_views(&con, &prep_pb)?;
         }
         prep_pb.inc(1);
         
         prep_pb.set_message("Computing statistics...");
-        compute_mv_stats(&con, &mut mvs)?;
+        let predicate_pairs = predicate_column_pairs(&queries);
+        compute_mv_stats(&con, &mut mvs, &predicate_pairs)?;
         prep_pb.inc(1);
         
         prep_pb.set_message("Planning and preparing queries...");
         let planner = Planner::new(&con);
         
-        let sql_queries: Vec<String> = queries.iter()
-            .map(|q| planner.translate_query(q, &mut mvs, false).unwrap_or_else(|_| {
+        // Planning time covers the planner and DuckDB's prepare for each query
+        let mut sql_queries = Vec::new();
+        let mut prepared_statements = Vec::new();
+        let mut planning_ms = Vec::new();
+        for q in &queries {
+            let plan_start = Instant::now();
+            let sql = planner.translate_query(q, &mut mvs, false).unwrap_or_else(|_| {
                 // Fallback to plain SQL if planner fails
-                query_handler::assemble_sql(q)
-            }))
+                calhacks::query_handler::assemble_sql(q)
+            });
+            prepared_statements.push(prepare_query(&con, &sql)?);
+            planning_ms.push(plan_start.elapsed().as_secs_f64() * 1000.0);
+            sql_queries.push(sql);
+        }
+        let chosen_mvs: Vec<Option<String>> = queries.iter()
+            .map(|q| planner.choose_mv(q, &mvs).map(|i| mvs[i].name.clone()))
             .collect();
         
-        let mut prepared_statements: Vec<_> = sql_queries
-            .iter()
-            .map(|sql| prepare_query(&con, sql))
-            .collect::<Result<Vec<_>, _>>()?;
-        
+        let mut profiles = Vec::new();
         if args.profile {
             for (i, sql) in sql_queries.iter().enumerate() {
-                explain_query(&con, sql, i + 1)?;
+                if let Some(profile) = explain_query(&con, sql, i + 1, &prep_pb)? {
+                    profiles.push((i + 1, profile, chosen_mvs[i].clone()));
+                }
             }
         }
         
-        prep_pb.set_message("Warming up database...");
-        warmup_cache(&con, &mvs)?;
+        if args.bench_mode == BenchMode::Warm {
+            prep_pb.set_message("Warming up database...");
+            warmup_cache(&con, &mvs)?;
+        }
         prep_pb.inc(1);
+        let duckdb_version = con.version()?;
         
         prep_pb.finish_and_clear();
         let prep_duration = prep_start.elapsed();
         println!("Query preparation and warmup completed in {}", format_duration_seconds(prep_duration));
+
+        if !profiles.is_empty() {
+            let summary = WorkloadProfileSummary::from_profiles(&profiles);
+            summary.print();
+            let summary_path = Path::new("profiling").join("summary.json");
+            summary.write(&summary_path)?;
+            println!("Profile summary written to {}", summary_path.display());
+        }
         
-        // Part 4: Query execution progress bar
-        let exec_start = Instant::now();
-        let exec_pb = ProgressBar::new(args.runs as u64);
-        exec_pb.set_style(
-            ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} Running queries...")
-                .unwrap()
-                .progress_chars("#>-")
-        );
-        
-        let mut total_duration = Duration::ZERO;
-        
-        let num_queries = prepared_statements.len();
-        let mut query_times = vec![Vec::new(); num_queries];
+        let run_opts = RunOptions {
+            runs,
+            output_dir,
+            format: args.output_format,
+            timing: args.timing,
+            discard_output: args.discard_output,
+            query_timeout: args.query_timeout.map(Duration::from_secs_f64),
+            global_deadline: args.global_timeout.map(|t| Instant::now() + Duration::from_secs_f64(t)),
+            duckdb_settings: &query_settings,
+        };
         
-        for run in 1..=args.runs {
-            con.execute("BEGIN TRANSACTION", [])?;
+        // Part 4: Query execution progress bar
+        let (query_times, parallel_stats, exec_duration) = if args.bench_mode != BenchMode::Warm {
+            // Each run opens its own database instance; close ours so the file isn't held open twice
+            drop(prepared_statements);
+            drop(con);
+
+            let exec_start = Instant::now();
+            let exec_pb = ProgressBar::new(runs as u64);
+            exec_pb.set_style(
+                ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} Running queries...")
+                    .unwrap()
+                    .progress_chars("#>-")
+            );
+
+            let query_times = run_queries_cold(&db_path, &sql_queries, args.drop_caches, &run_opts, &exec_pb)?;
+
+            exec_pb.finish_and_clear();
+            (query_times, None, exec_start.elapsed())
+        } else if args.connections > 1 {
+            let exec_pb = ProgressBar::new((runs * sql_queries.len()) as u64);
+            exec_pb.set_style(
+                ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} Running queries...")
+                    .unwrap()
+                    .progress_chars("#>-")
+            );
+
+            let stats = run_queries_parallel(&con, &sql_queries, args.connections, &run_opts, &exec_pb)?;
+
+            exec_pb.finish_and_clear();
+            let wall_time = stats.wall_time;
+            (stats.query_times.clone(), Some(stats), wall_time)
+        } else {
+            let exec_start = Instant::now();
+            let exec_pb = ProgressBar::new(runs as u64);
+            exec_pb.set_style(
+                ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} Running queries...")
+                    .unwrap()
+                    .progress_chars("#>-")
+            );
             
-            for (i, stmt) in prepared_statements.iter_mut().enumerate() {
-                let query_start = Instant::now();
-                let rows = stmt.query([])?;
-                let duration = query_start.elapsed();
-                
-                query_times[i].push(duration.as_secs_f64());
-                
-                total_duration += duration;
+            let num_queries = prepared_statements.len();
+            let mut query_times = vec![Vec::new(); num_queries];
+            let watchdog = run_opts.has_timeout().then(|| QueryWatchdog::new(&con));
+            
+            for run in 1..=runs {
+                con.execute("BEGIN TRANSACTION", [])?;
                 
-                if run == 1 {
-                    write_single_result_to_csv(i + 1, rows, output_dir)?;
+                for (i, stmt) in prepared_statements.iter_mut().enumerate() {
+                    let times = execute_timed(stmt, i + 1, run == 1, &run_opts, watchdog.as_ref())?;
+                    if times.timed_out {
+                        // An interrupted query aborts the transaction; start a fresh one
+                        con.execute("ROLLBACK", [])?;
+                        con.execute("BEGIN TRANSACTION", [])?;
+                    }
+                    query_times[i].push(times);
                 }
+                
+                con.execute("COMMIT", [])?;
+                exec_pb.inc(1);
             }
             
-            con.execute("COMMIT", [])?;
-            exec_pb.inc(1);
-        }
-        
-        exec_pb.finish_and_clear();
-        let exec_duration = exec_start.elapsed();
+            exec_pb.finish_and_clear();
+            (query_times, None, exec_start.elapsed())
+        };
         println!("Query execution completed in {}", format_duration_seconds(exec_duration));
         
         // Part 5: Summary
-        println!("\n=== Query Performance Summary ===");
-        let mut sum_of_averages_ns = 0u64;
+        match &parallel_stats {
+            Some(_) => println!("\n=== Query Performance Summary ({}, {} connections) ===", args.bench_mode.label(), args.connections),
+            None => println!("\n=== Query Performance Summary ({}) ===", args.bench_mode.label()),
+        }
+        let mut sum_of_averages_ms = 0.0;
+        let mut query_reports = Vec::new();
         for (i, times) in query_times.iter().enumerate() {
-            // Convert f64 seconds to nanoseconds for averaging
-            let avg_ns = (times.iter().sum::<f64>() / times.len() as f64 * 1_000_000_000.0) as u64;
-            sum_of_averages_ns = sum_of_averages_ns.saturating_add(avg_ns);
-            let avg_duration = Duration::from_nanos(avg_ns);
-            println!("Query {}: {} average", i + 1, format_duration_ms_ns(avg_duration));
+            // Timed-out executions are counted separately and kept out of the latency stats
+            let timeouts = times.iter().filter(|t| t.timed_out).count();
+            let samples_ms: Vec<f64> = times.iter().filter(|t| !t.timed_out).map(|t| t.total() * 1000.0).collect();
+            let stats = QueryStats::from_samples(&samples_ms);
+            sum_of_averages_ms += stats.mean_ms;
+
+            let qps = parallel_stats.as_ref().map(|p| p.query_qps(i));
+            if samples_ms.is_empty() {
+                println!("Query {}: TIMED OUT ({}/{} runs)", i + 1, timeouts, times.len());
+            } else {
+                println!(
+                    "Query {}: {} average | min {} p50 {} p90 {} p99 {} max {} | sd {} cv {:.1}% | {} outliers{}",
+                    i + 1,
+                    format_ms(stats.mean_ms),
+                    format_ms(stats.min_ms),
+                    format_ms(stats.p50_ms),
+                    format_ms(stats.p90_ms),
+                    format_ms(stats.p99_ms),
+                    format_ms(stats.max_ms),
+                    format_ms(stats.stddev_ms),
+                    stats.cv * 100.0,
+                    stats.outliers,
+                    qps.map(|q| format!(" | {:.1} QPS", q)).unwrap_or_default()
+                );
+                if timeouts > 0 {
+                    println!("  {}/{} runs timed out", timeouts, times.len());
+                }
+            }
+
+            let phases = (args.timing == TimingMode::EndToEnd).then(|| PhaseReport::from_times(times));
+            if let Some(phases) = &phases {
+                println!(
+                    "  phases (avg): planning {}, execute {}, fetch {}, serialize {} | {} rows",
+                    format_ms(planning_ms[i]),
+                    format_ms(phases.execution.mean_ms),
+                    format_ms(phases.fetch.mean_ms),
+                    format_ms(phases.serialization.mean_ms),
+                    phases.rows
+                );
+            }
+
+            query_reports.push(QueryReport {
+                query: i + 1,
+                sql: sql_queries[i].clone(),
+                mv: chosen_mvs[i].clone(),
+                planning_ms: planning_ms[i],
+                stats,
+                timeouts,
+                phases,
+                qps,
+                samples_ms,
+            });
+        }
+        println!("Sum of averages: {}", format_ms(sum_of_averages_ms));
+        if let Some(p) = &parallel_stats {
+            println!(
+                "Total: {} queries in {}, {:.1} QPS, {} mean latency",
+                p.total_executions(),
+                format_duration_seconds(p.wall_time),
+                p.qps(),
+                format_duration_ms_ns(Duration::from_secs_f64(p.mean_latency()))
+            );
+        }
+
+        let report = BenchmarkReport {
+            created_at: chrono::Local::now().to_rfc3339(),
+            queries_file: args.queries.display().to_string(),
+            mode: args.bench_mode,
+            timing: args.timing,
+            runs,
+            connections: args.connections,
+            environment: EnvironmentInfo::collect(
+                hw,
+                duckdb_version,
+                effective_preprocess_settings,
+                Some(effective_query_settings),
+            ),
+            queries: query_reports,
+            sum_of_averages_ms,
+            execution_time_ms: exec_duration.as_secs_f64() * 1000.0,
+            qps: parallel_stats.as_ref().map(|p| p.qps()),
+        };
+        let report_path = args.report.clone().unwrap_or_else(|| output_dir.join("benchmark.json"));
+        report.write(&report_path)?;
+        println!("Benchmark report written to {}", report_path.display());
+
+        let mut regressed_queries = Vec::new();
+        if let (Some(previous_path), Some(previous)) = (&args.compare_report, previous) {
+            let deltas = compare_reports(&previous, &report, args.regression_threshold, args.significance);
+
+            println!("\n=== Comparison against {} ({}) ===", previous_path.display(), previous.created_at);
+            if previous.mode != report.mode {
+                println!(
+                    "Warning: saved report used {} mode, this run used {} mode",
+                    previous.mode.label(),
+                    report.mode.label()
+                );
+            }
+            if previous.timing != report.timing {
+                println!("Warning: saved report used {:?} timing, this run used {:?} timing", previous.timing, report.timing);
+            }
+            println!("{:<8} {:>12} {:>12} {:>10} {:>10}  Status", "Query", "Previous p50", "Current p50", "Delta", "p-value");
+            for delta in &deltas {
+                let status = if delta.regressed {
+                    "REGRESSED"
+                } else if delta.improved {
+                    "improved"
+                } else if delta.insufficient_samples {
+                    "insufficient samples"
+                } else {
+                    "unchanged"
+                };
+                println!(
+                    "{:<8} {:>12} {:>12} {:>9.1}% {:>10.4}  {}{}",
+                    format!("Query {}", delta.query),
+                    format_ms(delta.previous_p50_ms),
+                    if delta.current_p50_ms.is_finite() { format_ms(delta.current_p50_ms) } else { "timed out".to_string() },
+                    delta.delta_pct,
+                    delta.p_value,
+                    status,
+                    if delta.mv_changed { " (MV changed)" } else { "" }
+                );
+                if delta.regressed {
+                    regressed_queries.push(delta.query);
+                }
+            }
+            let insufficient: Vec<usize> = deltas.iter().filter(|d| d.insufficient_samples).map(|d| d.query).collect();
+            if !insufficient.is_empty() {
+                println!(
+                    "Warning: queries {:?} have too few completed runs for the comparison to reach --significance {}; \
+                     regressions there cannot be detected",
+                    insufficient, args.significance
+                );
+            }
+        }
+
+        if let Some(baseline_dir) = baseline_dir {
+            compare_results(baseline_dir, output_dir, &queries, &tolerances)?;
         }
-        let sum_avg_duration = Duration::from_nanos(sum_of_averages_ns);
-        println!("Sum of averages: {}", format_duration_ms_ns(sum_avg_duration));
 
-        if let Some(baseline_dir) = &args.baseline_dir {
-            compare_results(baseline_dir, output_dir)?;
+        if !regressed_queries.is_empty() {
+            anyhow::bail!(
+                "{} queries regressed by more than {}%: {:?}",
+                regressed_queries.len(),
+                args.regression_threshold,
+                regressed_queries
+            );
         }
     }
 
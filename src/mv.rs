@@ -1,5 +1,7 @@
 use std::collections::HashSet;
 
+use crate::schema::EventSchema;
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Agg {
     pub op: String,
@@ -23,6 +25,11 @@ pub struct MaterializedView {
     pub num_rows: Option<i64>,
     pub num_distinct: std::collections::HashMap<String, i64>,
     pub col_to_topk: std::collections::HashMap<String, std::collections::HashMap<String, i64>>,
+    /// Joint distinct counts for column pairs that co-occur in workload predicates.
+    /// Keys are ordered `(a, b)` with `a < b`.
+    pub pair_num_distinct: std::collections::HashMap<(String, String), i64>,
+    /// Joint top-k value combinations for the same column pairs
+    pub pair_to_topk: std::collections::HashMap<(String, String), std::collections::HashMap<(String, String), i64>>,
 }
 
 impl MaterializedView {
@@ -34,7 +41,48 @@ impl MaterializedView {
             num_rows: None,
             num_distinct: std::collections::HashMap::new(),
             col_to_topk: std::collections::HashMap::new(),
+            pair_num_distinct: std::collections::HashMap::new(),
+            pair_to_topk: std::collections::HashMap::new(),
+        }
+    }
+
+    /// Partition column value held by a partitioned MV (named `<base>_<column>_<value>`, e.g.
+    /// `mv_day_fast_type_click`), or None for base MVs. Base names like `mv_type_day_minute`
+    /// also contain `_type_`, so only a known value as the final segment counts.
+    pub fn partition_value(&self) -> Option<&str> {
+        let schema = crate::schema::get();
+        let column = schema.partition_column.as_deref()?;
+        let (_, suffix) = self.name.rsplit_once(&format!("_{}_", column))?;
+        schema.partition_values().iter().any(|v| v == suffix).then_some(suffix)
+    }
+
+    /// Columns this MV needs that the event schema lacks, or that it aggregates but are not numeric
+    pub fn schema_violations(&self, schema: &EventSchema) -> Vec<String> {
+        let mut violations: Vec<String> = self
+            .group_by
+            .iter()
+            .filter(|col| !schema.has_column(col))
+            .map(|col| format!("unknown column {}", col))
+            .collect();
+        for agg in &self.aggs {
+            let Some(col) = &agg.column else {
+                continue;
+            };
+            if !schema.has_column(col) {
+                violations.push(format!("unknown column {}", col));
+            } else if agg.op != "COUNT" && !schema.is_numeric(col) {
+                violations.push(format!("{}({}) over a non-numeric column", agg.op, col));
+            }
         }
+        violations.sort();
+        violations.dedup();
+        violations
+    }
+
+    /// Approximate on-disk footprint: every column counted as 8 bytes per row
+    pub fn estimated_bytes(&self) -> f64 {
+        let columns = self.group_by.len() + self.aggs.len();
+        self.num_rows.unwrap_or(0) as f64 * columns as f64 * 8.0
     }
 
     pub fn has_stats(&self) -> bool {
@@ -61,10 +109,12 @@ impl MaterializedView {
             .collect();
 
         // Determine optimal sort order for filtering
-        // Priority: type (most common filter), then day, then other dimensions
+        // Priority: partition column (type; most common filter), then day, then other dimensions
         let mut order_by_cols = Vec::new();
-        if self.group_by.contains(&"type".to_string()) {
-            order_by_cols.push("type".to_string());
+        if let Some(partition_column) = &crate::schema::get().partition_column
+            && self.group_by.contains(partition_column)
+        {
+            order_by_cols.push(partition_column.clone());
         }
         if self.group_by.contains(&"day".to_string()) {
             order_by_cols.push("day".to_string());
@@ -206,28 +256,35 @@ pub fn create_mv_registry() -> Vec<MaterializedView> {
     registry
 }
 
-/// Create type-partitioned versions of MVs that have 'type' in group_by
-/// This allows queries filtering by type to scan much smaller tables
-pub fn create_type_partitioned_mvs(base_mvs: &[MaterializedView]) -> Vec<MaterializedView> {
+/// Base MVs with fewer rows than this are not worth splitting by type
+pub const MIN_PARTITION_ROWS: i64 = 100_000;
+
+/// Create partitioned versions of MVs that have the schema's partition column (`type` for ad
+/// events) in group_by, one per value. This allows queries filtering by type to scan much
+/// smaller tables. MVs with fewer than `min_rows` rows are skipped.
+pub fn create_type_partitioned_mvs(base_mvs: &[MaterializedView], min_rows: i64) -> Vec<MaterializedView> {
+    let schema = crate::schema::get();
+    let Some(partition_column) = &schema.partition_column else {
+        return Vec::new();
+    };
+
     let mut partitioned = Vec::new();
-    let types = vec!["impression", "click", "serve", "purchase"];
-    
     for mv in base_mvs {
-        // Only partition MVs that have 'type' in group_by and are commonly filtered
-        if mv.group_by.contains(&"type".to_string()) {
+        // Only partition MVs that have the partition column in group_by
+        if mv.group_by.contains(partition_column) {
             // Skip very small MVs (not worth partitioning)
             if let Some(rows) = mv.num_rows {
-                if rows < 100_000 {
+                if rows < min_rows {
                     continue; // Too small to benefit from partitioning
                 }
             }
             
-            // Create a partitioned MV for each type
-            for event_type in &types {
-                let partitioned_name = format!("{}_type_{}", mv.name, event_type);
+            // Create a partitioned MV for each value
+            for value in schema.partition_values() {
+                let partitioned_name = format!("{}_{}_{}", mv.name, partition_column, value);
                 let mut partitioned_group_by = mv.group_by.clone();
-                // Remove 'type' from group_by since it's now constant
-                partitioned_group_by.retain(|x| x != "type");
+                // Remove the partition column from group_by since it's now constant
+                partitioned_group_by.retain(|x| x != partition_column);
                 
                 partitioned.push(MaterializedView::new(
                     &partitioned_name,
@@ -0,0 +1,132 @@
+use anyhow::Result;
+use duckdb::Connection;
+use indicatif::ProgressBar;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::query_executor::{PhaseTimes, RunOptions, execute_timed, prepare_query};
+use crate::watchdog::QueryWatchdog;
+
+/// Timings collected from running the workload on a pool of connections
+pub struct ParallelRunStats {
+    /// Per-query timings
+    pub query_times: Vec<Vec<PhaseTimes>>,
+    /// Per-query wall-clock span from first start to last completion
+    pub query_spans: Vec<Duration>,
+    /// Wall-clock time for the whole run
+    pub wall_time: Duration,
+}
+
+impl ParallelRunStats {
+    /// Executions that completed (timed-out executions are excluded from throughput)
+    pub fn total_executions(&self) -> usize {
+        self.query_times.iter().flatten().filter(|t| !t.timed_out).count()
+    }
+
+    /// Aggregate throughput over the whole run
+    pub fn qps(&self) -> f64 {
+        self.total_executions() as f64 / self.wall_time.as_secs_f64().max(f64::EPSILON)
+    }
+
+    /// Throughput of a single query over the span it was being executed
+    pub fn query_qps(&self, query_idx: usize) -> f64 {
+        let completed = self.query_times[query_idx].iter().filter(|t| !t.timed_out).count();
+        completed as f64 / self.query_spans[query_idx].as_secs_f64().max(f64::EPSILON)
+    }
+
+    /// Mean latency across every execution, in seconds
+    pub fn mean_latency(&self) -> f64 {
+        let total: f64 = self.query_times.iter().flatten().filter(|t| !t.timed_out).map(|t| t.total()).sum();
+        total / self.total_executions().max(1) as f64
+    }
+}
+
+/// Run every query `runs` times across `num_connections` connections cloned from `con`.
+///
+/// Jobs are handed out run by run (all queries of run 1, then run 2, ...) from a shared
+/// counter, so every connection stays busy until the workload is drained. Timing follows
+/// the same mode as the serial loop, and the result of each query's first run is written
+/// to `qN.<ext>` by whichever worker executed it, so output files are deterministic.
+pub fn run_queries_parallel(
+    con: &Connection,
+    sql_queries: &[String],
+    num_connections: usize,
+    opts: &RunOptions,
+    pb: &ProgressBar,
+) -> Result<ParallelRunStats> {
+    let num_queries = sql_queries.len();
+    let total_jobs = opts.runs * num_queries;
+    let next_job = AtomicUsize::new(0);
+
+    let connections = (0..num_connections.max(1))
+        .map(|_| con.try_clone())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let run_start = Instant::now();
+
+    let worker_results: Vec<Result<Vec<(usize, Instant, PhaseTimes)>>> = std::thread::scope(|s| {
+        let handles: Vec<_> = connections
+            .into_iter()
+            .map(|worker_con| {
+                let next_job = &next_job;
+                s.spawn(move || -> Result<Vec<(usize, Instant, PhaseTimes)>> {
+                    let watchdog = opts.has_timeout().then(|| QueryWatchdog::new(&worker_con));
+                    let mut statements = sql_queries
+                        .iter()
+                        .map(|sql| prepare_query(&worker_con, sql))
+                        .collect::<Result<Vec<_>>>()?;
+
+                    let mut timings = Vec::new();
+                    loop {
+                        let job = next_job.fetch_add(1, Ordering::Relaxed);
+                        if job >= total_jobs {
+                            break;
+                        }
+                        let (run, query_idx) = (job / num_queries, job % num_queries);
+
+                        let query_start = Instant::now();
+                        let times = execute_timed(&mut statements[query_idx], query_idx + 1, run == 0, opts, watchdog.as_ref())?;
+                        timings.push((query_idx, query_start, times));
+                        pb.inc(1);
+                    }
+                    Ok(timings)
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap_or_else(|_| Err(anyhow::anyhow!("Query worker panicked"))))
+            .collect()
+    });
+
+    let wall_time = run_start.elapsed();
+
+    let mut query_times = vec![Vec::new(); num_queries];
+    let mut first_start: Vec<Option<Instant>> = vec![None; num_queries];
+    let mut last_end: Vec<Option<Instant>> = vec![None; num_queries];
+
+    for result in worker_results {
+        for (query_idx, start, times) in result? {
+            let end = start + Duration::from_secs_f64(times.total());
+            query_times[query_idx].push(times);
+            first_start[query_idx] = Some(first_start[query_idx].map_or(start, |t| t.min(start)));
+            last_end[query_idx] = Some(last_end[query_idx].map_or(end, |t| t.max(end)));
+        }
+    }
+
+    let query_spans = first_start
+        .iter()
+        .zip(&last_end)
+        .map(|(start, end)| match (start, end) {
+            (Some(start), Some(end)) => *end - *start,
+            _ => Duration::ZERO,
+        })
+        .collect();
+
+    Ok(ParallelRunStats {
+        query_times,
+        query_spans,
+        wall_time,
+    })
+}
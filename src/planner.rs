@@ -4,6 +4,7 @@ use serde_json::Value;
 use std::collections::HashSet;
 
 use crate::mv::{Agg, MaterializedView, metric_col_name};
+use crate::query_handler::format_value_for_sql;
 
 pub struct Planner;
 
@@ -27,26 +28,17 @@ impl Planner {
     }
 
     pub fn is_mv_usable(&self, query: &Value, mv: &MaterializedView) -> bool {
-        // Check if this is a type-partitioned MV
-        let is_type_partitioned = mv.name.contains("_type_") && {
-            let parts: Vec<&str> = mv.name.split("_type_").collect();
-            if parts.len() == 2 {
-                let type_part = parts[1];
-                matches!(type_part, "click" | "impression" | "purchase" | "serve")
-            } else {
-                false
-            }
-        };
-        let query_type = self.extract_type_filter(query);
+        // Check if this is a partitioned MV (e.g. one per event type)
+        let partition_column = crate::schema::get().partition_column.as_deref();
+        let partition_value = mv.partition_value();
+        let is_type_partitioned = partition_value.is_some();
+        let query_type = self.extract_partition_filter(query);
         
-        // For type-partitioned MVs, check if the type matches
-        if is_type_partitioned {
+        // For partitioned MVs, check if the partition value matches
+        if let Some(mv_type) = partition_value {
             if let Some(qtype) = &query_type {
-                // Extract type from MV name (format: mv_name_type_impression)
-                if let Some(mv_type) = mv.name.split("_type_").last() {
-                    if mv_type != qtype {
-                        return false; // Type doesn't match
-                    }
+                if mv_type != qtype {
+                    return false; // Type doesn't match
                 }
             } else {
                 return false; // Query doesn't filter by type, can't use partitioned MV
@@ -65,23 +57,23 @@ impl Planner {
             })
             .unwrap_or_default();
 
-        // For type-partitioned MVs, 'type' is not in group_by but is filtered
+        // For partitioned MVs, the partition column is not in group_by but is filtered
         let mut mv_group_by: HashSet<String> = mv.group_by.iter().cloned().collect();
-        if is_type_partitioned {
-            // Add 'type' back for WHERE clause checking
-            mv_group_by.insert("type".to_string());
+        if let Some(column) = partition_column.filter(|_| is_type_partitioned) {
+            // Add it back for WHERE clause checking
+            mv_group_by.insert(column.to_string());
         }
         
         if !q_group_by.is_subset(&mv_group_by) {
             return false;
         }
 
-        // Check WHERE columns exist in MV (excluding type for partitioned MVs)
+        // Check WHERE columns exist in MV (excluding the partition column for partitioned MVs)
         if let Some(where_arr) = query.get("where").and_then(|v| v.as_array()) {
             for pred in where_arr {
                 if let Some(col) = pred.get("col").and_then(|v| v.as_str()) {
-                    if col == "type" && is_type_partitioned {
-                        // Type is already filtered in partitioned MV, skip
+                    if Some(col) == partition_column && is_type_partitioned {
+                        // Already filtered in partitioned MV, skip
                         continue;
                     }
                     if !mv_group_by.contains(col) {
@@ -198,12 +190,85 @@ impl Planner {
         0.1 // Default selectivity
     }
 
+    /// Values a predicate pins its column to (`eq` or `in`), as they appear in top-k stats
+    fn point_values(&self, pred: &Value) -> Option<Vec<String>> {
+        let op = pred.get("op").and_then(|v| v.as_str()).unwrap_or("");
+        let val = pred.get("val")?;
+        let as_string = |v: &Value| v.as_str().map(|s| s.to_string()).or_else(|| v.as_i64().map(|n| n.to_string()));
+
+        match op {
+            "eq" => as_string(val).map(|s| vec![s]),
+            "in" => val.as_array()?.iter().map(as_string).collect(),
+            _ => None,
+        }
+    }
+
+    /// Joint selectivity of two point predicates on a column pair with collected pair stats.
+    /// Returns None when the MV has no joint stats for the pair, so the caller falls back
+    /// to multiplying per-column selectivities.
+    fn pair_selectivity(&self, p1: &Value, p2: &Value, mv: &MaterializedView) -> Option<f64> {
+        let col1 = p1.get("col").and_then(|v| v.as_str())?;
+        let col2 = p2.get("col").and_then(|v| v.as_str())?;
+        if col1 == col2 {
+            return None;
+        }
+
+        let ((col_a, pred_a), (col_b, pred_b)) = if col1 < col2 {
+            ((col1, p1), (col2, p2))
+        } else {
+            ((col2, p2), (col1, p1))
+        };
+        let key = (col_a.to_string(), col_b.to_string());
+        let distinct = *mv.pair_num_distinct.get(&key)?;
+        let a_values = self.point_values(pred_a)?;
+        let b_values = self.point_values(pred_b)?;
+
+        let num_rows = mv.num_rows.unwrap_or(1).max(1) as f64;
+        let topk = mv.pair_to_topk.get(&key);
+
+        // Combinations outside the top-k are assumed uniform over the observed combinations,
+        // but can never be more frequent than the least frequent top-k entry
+        let mut unseen_count = num_rows / distinct.max(1) as f64;
+        if let Some(min_topk) = topk.and_then(|t| t.values().min()) {
+            unseen_count = unseen_count.min(*min_topk as f64);
+        }
+
+        let mut count = 0.0;
+        for a in &a_values {
+            for b in &b_values {
+                count += match topk.and_then(|t| t.get(&(a.clone(), b.clone()))) {
+                    Some(&c) => c as f64,
+                    None => unseen_count,
+                };
+            }
+        }
+
+        Some((count / num_rows).min(1.0))
+    }
+
     pub fn mv_cost(&self, query: &Value, mv: &MaterializedView) -> f64 {
-        // Compute selectivity from WHERE clauses
+        // Compute selectivity from WHERE clauses, using joint stats for correlated pairs
+        // and falling back to independence for the remaining predicates
         let mut selectivity = 1.0;
         if let Some(where_arr) = query.get("where").and_then(|v| v.as_array()) {
-            for pred in where_arr {
-                selectivity *= self.predicate_selectivity(pred, mv);
+            let mut used = vec![false; where_arr.len()];
+            for i in 0..where_arr.len() {
+                for j in i + 1..where_arr.len() {
+                    if used[i] || used[j] {
+                        continue;
+                    }
+                    if let Some(sel) = self.pair_selectivity(&where_arr[i], &where_arr[j], mv) {
+                        selectivity *= sel;
+                        used[i] = true;
+                        used[j] = true;
+                    }
+                }
+            }
+
+            for (pred, used) in where_arr.iter().zip(used) {
+                if !used {
+                    selectivity *= self.predicate_selectivity(pred, mv);
+                }
             }
         }
 
@@ -238,6 +303,7 @@ impl Planner {
         use crate::hardware::get_hardware_info;
         let hw = get_hardware_info();
         let (scan_weight, rollup_weight) = hw.cost_weights();
+        let scan_weight = hw.scan_weight_for(scan_weight, mv.estimated_bytes());
         
         let base_cost = scan_weight * num_rows_scanned + rollup_weight * num_groups;
 
@@ -263,8 +329,18 @@ impl Planner {
     }
 
     pub fn translate_query(&self, query: &Value, mvs: &mut [MaterializedView], _verbose: bool) -> Result<String> {
+        if let Some(idx) = self.choose_mv(query, mvs) {
+            let mv = &mvs[idx];
+            Ok(self.assemble_sql_for_mv(query, mv))
+        } else {
+            Ok(self.assemble_sql_plain(query))
+        }
+    }
+
+    /// Index of the cheapest usable MV for `query`, or None to fall back to raw events
+    pub fn choose_mv(&self, query: &Value, mvs: &[MaterializedView]) -> Option<usize> {
         // Check if query filters by type - if so, prefer type-partitioned MVs
-        let query_type = self.extract_type_filter(query);
+        let query_type = self.extract_partition_filter(query);
         
         let mut best_mv: Option<usize> = None;
         let mut best_cost = f64::INFINITY;
@@ -279,20 +355,11 @@ impl Planner {
                 let cost = self.mv_cost(query, mv);
                 
                 // Prefer type-partitioned MVs when query filters by type
-                // Type-partitioned MVs have format: mv_name_type_<type> (e.g., mv_advertiser_id_full_type_impression)
-                // Base MVs with type in name have format: mv_type_* (e.g., mv_type_week_day)
-                let is_type_partitioned_mv = mv.name.contains("_type_") && {
-                    let parts: Vec<&str> = mv.name.split("_type_").collect();
-                    if parts.len() == 2 {
-                        let type_part = parts[1];
-                        matches!(type_part, "click" | "impression" | "purchase" | "serve")
-                    } else {
-                        false
-                    }
-                };
+                let partition_type = mv.partition_value();
+                let is_type_partitioned_mv = partition_type.is_some();
                 
                 let adjusted_cost = if let Some(qtype) = &query_type {
-                    if is_type_partitioned_mv && mv.name.contains(&format!("_type_{}", qtype)) {
+                    if partition_type == Some(qtype.as_str()) {
                         // Type-partitioned MV matches query type - significant cost reduction
                         cost * 0.1 // 90% cost reduction for exact type match
                     } else if is_type_partitioned_mv {
@@ -312,19 +379,16 @@ impl Planner {
             }
         }
 
-        if let Some(idx) = best_mv {
-            let mv = &mvs[idx];
-            Ok(self.assemble_sql_for_mv(query, mv))
-        } else {
-            Ok(self.assemble_sql_plain(query))
-        }
+        best_mv
     }
     
-    fn extract_type_filter(&self, query: &Value) -> Option<String> {
+    /// Value of an equality filter on the schema's partition column (e.g. `type = 'impression'`)
+    fn extract_partition_filter(&self, query: &Value) -> Option<String> {
+        let partition_column = crate::schema::get().partition_column.as_deref()?;
         if let Some(where_arr) = query.get("where").and_then(|v| v.as_array()) {
             for pred in where_arr {
                 if let Some(col) = pred.get("col").and_then(|v| v.as_str()) {
-                    if col == "type" {
+                    if col == partition_column {
                         if let Some(op) = pred.get("op").and_then(|v| v.as_str()) {
                             if op == "eq" {
                                 return pred.get("val").and_then(|v| v.as_str()).map(|s| s.to_string());
@@ -341,10 +405,9 @@ impl Planner {
         let select_sql = self.select_over_mv(query.get("select"), mv);
         let from_tbl = mv.name.clone();
         
-        // For type-partitioned MVs, exclude type filter from WHERE clause
-        let is_type_partitioned = mv.name.contains("_type_");
-        let where_clause = if is_type_partitioned {
-            self.where_to_sql_excluding_type(query.get("where"))
+        // For partitioned MVs, exclude the partition filter from WHERE clause
+        let where_clause = if mv.partition_value().is_some() {
+            self.where_to_sql_excluding_partition(query.get("where"))
         } else {
             self.where_to_sql(query.get("where"))
         };
@@ -368,16 +431,17 @@ impl Planner {
         sql
     }
     
-    fn where_to_sql_excluding_type(&self, where_clause: Option<&Value>) -> String {
+    fn where_to_sql_excluding_partition(&self, where_clause: Option<&Value>) -> String {
+        let partition_column = crate::schema::get().partition_column.as_deref();
         let Some(conditions) = where_clause.and_then(|w| w.as_array()) else {
             return String::new();
         };
 
         let parts: Vec<String> = conditions.iter()
             .filter_map(|cond| {
-                // Skip type filters for partitioned MVs
+                // Skip partition column filters for partitioned MVs
                 if let Some(col) = cond.get("col").and_then(|v| v.as_str()) {
-                    if col == "type" {
+                    if Some(col) == partition_column {
                         return None;
                     }
                 }
@@ -413,6 +477,16 @@ impl Planner {
                     format!("{} != {}", col, val.unwrap_or(&serde_json::Value::Null))
                 }
             }
+            "lt" | "lte" | "gt" | "gte" => {
+                // Same literal formatting as the raw SQL so both sides compare identically
+                let sql_op = match op {
+                    "lt" => "<",
+                    "lte" => "<=",
+                    "gt" => ">",
+                    _ => ">=",
+                };
+                format!("{} {} {}", col, sql_op, format_value_for_sql(val.unwrap_or(&Value::Null)))
+            }
             "between" => {
                 if let Some(arr) = val.and_then(|v| v.as_array()) {
                     let low = arr[0].as_str().unwrap_or("");
@@ -449,7 +523,7 @@ impl Planner {
         for item in select_arr {
             if let Some(col_str) = item.as_str() {
                 // Cast ENUM types to VARCHAR for compatibility with Rust bindings
-                let col_expr = if col_str == "type" {
+                let col_expr = if crate::schema::get().is_enum(col_str) {
                     format!("CAST({} AS VARCHAR) AS {}", col_str, col_str)
                 } else {
                     col_str.to_string()
@@ -519,48 +593,10 @@ impl Planner {
             return String::new();
         };
 
-        let parts: Vec<String> = conditions.iter().map(|cond| {
-            let col = cond.get("col").and_then(|v| v.as_str()).unwrap_or("");
-            let op = cond.get("op").and_then(|v| v.as_str()).unwrap_or("");
-            let val = cond.get("val");
-
-            match op {
-                "eq" => {
-                    if let Some(s) = val.and_then(|v| v.as_str()) {
-                        format!("{} = '{}'", col, s)
-                    } else {
-                        format!("{} = {}", col, val.unwrap_or(&Value::Null))
-                    }
-                }
-                "neq" => {
-                    if let Some(s) = val.and_then(|v| v.as_str()) {
-                        format!("{} != '{}'", col, s)
-                    } else {
-                        format!("{} != {}", col, val.unwrap_or(&Value::Null))
-                    }
-                }
-                "between" => {
-                    if let Some(arr) = val.and_then(|v| v.as_array()) {
-                        let low = arr[0].as_str().unwrap_or("");
-                        let high = arr[1].as_str().unwrap_or("");
-                        format!("{} BETWEEN '{}' AND '{}'", col, low, high)
-                    } else {
-                        String::new()
-                    }
-                }
-                "in" => {
-                    if let Some(arr) = val.and_then(|v| v.as_array()) {
-                        let vals_str: Vec<String> = arr.iter()
-                            .map(|v| format!("'{}'", v.as_str().unwrap_or("")))
-                            .collect();
-                        format!("{} IN ({})", col, vals_str.join(", "))
-                    } else {
-                        String::new()
-                    }
-                }
-                _ => String::new(),
-            }
-        }).collect();
+        let parts: Vec<String> = conditions.iter()
+            .map(|cond| self.predicate_to_sql(cond))
+            .filter(|s| !s.is_empty())
+            .collect();
 
         if parts.is_empty() {
             String::new()
@@ -1,10 +1,20 @@
 use duckdb::Connection;
 use anyhow::Result;
+use indicatif::ProgressBar;
 
 use crate::mv::{create_mv_registry, MaterializedView, create_type_partitioned_mvs};
 
-pub fn create_materialized_views(con: &Connection) -> Result<Vec<MaterializedView>> {
-    let mvs = create_mv_registry();
+pub fn create_materialized_views(con: &Connection, pb: &ProgressBar) -> Result<Vec<MaterializedView>> {
+    // The registry is written for ad events; skip MVs over columns another schema lacks
+    let schema = crate::schema::get();
+    let mut mvs = create_mv_registry();
+    mvs.retain(|mv| {
+        let violations = mv.schema_violations(schema);
+        if !violations.is_empty() {
+            pb.println(format!("Warning: skipping MV {} ({})", mv.name, violations.join(", ")));
+        }
+        violations.is_empty()
+    });
     
     for mv in &mvs {
         let sql = mv.generate_create_sql();
@@ -14,23 +24,25 @@ pub fn create_materialized_views(con: &Connection) -> Result<Vec<MaterializedVie
     Ok(mvs)
 }
 
-pub fn create_type_partitioned_materialized_views(con: &Connection, base_mvs: &[MaterializedView]) -> Result<Vec<MaterializedView>> {
+pub fn create_type_partitioned_materialized_views(con: &Connection, base_mvs: &[MaterializedView], min_rows: i64) -> Result<Vec<MaterializedView>> {
     // First compute stats on base MVs to determine which ones to partition
     let mut mvs_with_stats = base_mvs.to_vec();
-    compute_mv_stats(con, &mut mvs_with_stats)?;
+    compute_mv_stats(con, &mut mvs_with_stats, &[])?;
     
-    let partitioned_mvs = create_type_partitioned_mvs(&mvs_with_stats);
+    let partitioned_mvs = create_type_partitioned_mvs(&mvs_with_stats, min_rows);
     
     if partitioned_mvs.is_empty() {
         return Ok(Vec::new());
     }
     
+    let Some(partition_column) = crate::schema::get().partition_column.as_deref() else {
+        return Ok(Vec::new());
+    };
     for mv in &partitioned_mvs {
-        // Extract type from name (format: mv_name_type_impression)
-        let event_type = mv.name.split("_type_").last().unwrap_or("unknown");
+        let event_type = mv.partition_value().unwrap_or("unknown");
         
         // Create SQL that filters by type and groups by remaining columns
-        // Note: We don't include 'type' in SELECT since it's constant (filtered in WHERE)
+        // Note: We don't include the partition column in SELECT since it's constant (filtered in WHERE)
         let mut select_parts = mv.group_by.clone();
         
         for agg in &mv.aggs {
@@ -69,9 +81,10 @@ pub fn create_type_partitioned_materialized_views(con: &Connection, base_mvs: &[
         };
         
         let sql = format!(
-            "CREATE TABLE IF NOT EXISTS {} AS\nSELECT\n{}\nFROM events\nWHERE type = '{}'\nGROUP BY {}{};",
+            "CREATE TABLE IF NOT EXISTS {} AS\nSELECT\n{}\nFROM events\nWHERE {} = '{}'\nGROUP BY {}{};",
             mv.name,
             select_parts.join(",\n"),
+            partition_column,
             event_type,
             group_by_positions.join(", "),
             order_by_clause
@@ -83,7 +96,11 @@ pub fn create_type_partitioned_materialized_views(con: &Connection, base_mvs: &[
     Ok(partitioned_mvs)
 }
 
-pub fn compute_mv_stats(con: &Connection, mvs: &mut [MaterializedView]) -> Result<()> {
+/// Compute row counts, per-column distinct counts and top-k values for each MV.
+/// For every pair in `predicate_pairs` whose columns are both in the MV's group-by,
+/// also collect the joint distinct count and joint top-k so the planner can estimate
+/// correlated predicates (e.g. `country` and `publisher_id`) without assuming independence.
+pub fn compute_mv_stats(con: &Connection, mvs: &mut [MaterializedView], predicate_pairs: &[(String, String)]) -> Result<()> {
     for mv in mvs.iter_mut() {
         // We need to compute stats, but Planner::compute_mv_stats needs mutable access
         // For now, we'll compute stats directly here
@@ -121,6 +138,33 @@ pub fn compute_mv_stats(con: &Connection, mvs: &mut [MaterializedView]) -> Resul
             }
             mv.col_to_topk.insert(col.clone(), topk);
         }
+
+        // Joint stats for column pairs that co-occur in the workload's predicates
+        for (a, b) in predicate_pairs {
+            if !mv.group_by.contains(a) || !mv.group_by.contains(b) {
+                continue;
+            }
+
+            let sql = format!("SELECT COUNT(*) FROM (SELECT DISTINCT {}, {} FROM {})", a, b, mv.name);
+            let distinct: i64 = con.query_row(&sql, [], |row| row.get(0))?;
+            mv.pair_num_distinct.insert((a.clone(), b.clone()), distinct);
+
+            let sql = format!(
+                "SELECT CAST({} AS VARCHAR), CAST({} AS VARCHAR), COUNT(*) as cnt FROM {} GROUP BY 1, 2 ORDER BY cnt DESC LIMIT 10",
+                a, b, mv.name
+            );
+            let mut stmt = con.prepare(&sql)?;
+            let mut rows = stmt.query([])?;
+
+            let mut topk = std::collections::HashMap::new();
+            while let Some(row) = rows.next()? {
+                let a_value: String = row.get(0)?;
+                let b_value: String = row.get(1)?;
+                let count: i64 = row.get(2)?;
+                topk.insert((a_value, b_value), count);
+            }
+            mv.pair_to_topk.insert((a.clone(), b.clone()), topk);
+        }
     }
     
     Ok(())
@@ -218,6 +262,7 @@ pub fn load_all_mvs_from_db(con: &Connection) -> Result<Vec<MaterializedView>> {
     }
     
     // Reconstruct MV metadata from database schema
+    let schema = crate::schema::get();
     let mut mvs = Vec::new();
     for mv_name in mv_names {
         // Get column info for this MV
@@ -240,12 +285,14 @@ pub fn load_all_mvs_from_db(con: &Connection) -> Result<Vec<MaterializedView>> {
             let _data_type: String = col_row.get(1)?;
             
             // Determine if this is a group-by column or aggregate
-            // Group-by columns are typically: type, day, country, advertiser_id, etc.
+            // Group-by columns are event schema columns (raw or derived): type, day, country, etc.
             // Aggregates have names like: sum_bid_price, count_rows, etc.
-            if col_name.contains("_") && (col_name.starts_with("sum_") || 
-                                          col_name.starts_with("count_") ||
-                                          col_name.starts_with("min_") ||
-                                          col_name.starts_with("max_")) {
+            if schema.has_column(&col_name) {
+                group_by_cols.push(col_name);
+            } else if col_name.starts_with("sum_") || 
+                      col_name.starts_with("count_") ||
+                      col_name.starts_with("min_") ||
+                      col_name.starts_with("max_") {
                 // This is an aggregate column
                 let parts: Vec<&str> = col_name.splitn(2, '_').collect();
                 if parts.len() == 2 {
@@ -258,34 +305,14 @@ pub fn load_all_mvs_from_db(con: &Connection) -> Result<Vec<MaterializedView>> {
                     use crate::mv::Agg;
                     aggs.push(Agg::new(&op, col.as_deref()));
                 }
-            } else if !col_name.contains("_") || 
-                      col_name == "type" || 
-                      col_name == "day" || 
-                      col_name == "country" || 
-                      col_name == "week" ||
-                      col_name == "hour" ||
-                      col_name == "minute" ||
-                      col_name.contains("id") {
-                // This is likely a group-by column
-                group_by_cols.push(col_name);
-            }
-        }
-        
-        // Remove 'type' from group_by if this is a type-partitioned MV
-        // Type-partitioned MVs have format: mv_name_type_<type> (e.g., mv_advertiser_id_full_type_impression)
-        // Base MVs with type in name have format: mv_type_* (e.g., mv_type_week_day)
-        // We distinguish by checking if the pattern matches *_type_<type> where <type> is one of the known types
-        let is_type_partitioned = mv_name.contains("_type_") && {
-            let parts: Vec<&str> = mv_name.split("_type_").collect();
-            if parts.len() == 2 {
-                let type_part = parts[1];
-                matches!(type_part, "click" | "impression" | "purchase" | "serve")
             } else {
-                false
+                // Dropping it would silently change what the MV can answer
+                anyhow::bail!(
+                    "Column {} of {} is neither in the event schema nor an aggregate; was the database built with a different --schema?",
+                    col_name,
+                    mv_name
+                );
             }
-        };
-        if is_type_partitioned {
-            group_by_cols.retain(|x| x != "type");
         }
         
         use crate::mv::MaterializedView;
@@ -0,0 +1,224 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// How many operators to keep per query, by time
+const TOP_OPERATORS: usize = 5;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperatorSummary {
+    pub name: String,
+    pub timing_ms: f64,
+    /// Rows the operator produced
+    pub cardinality: u64,
+}
+
+/// One scan operator and what it read
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanSummary {
+    /// Table name, or the table function (e.g. READ_PARQUET) for the raw `events` view
+    pub source: String,
+    pub rows_scanned: u64,
+    /// Rows left after pushed-down filters
+    pub cardinality: u64,
+    pub timing_ms: f64,
+}
+
+/// What DuckDB reported for one query run under `--profile`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileSummary {
+    pub query: usize,
+    /// MV the planner chose, or None when the query was sent to raw `events`
+    pub chosen_mv: Option<String>,
+    pub latency_ms: f64,
+    pub rows_returned: u64,
+    pub top_operators: Vec<OperatorSummary>,
+    pub scans: Vec<ScanSummary>,
+    /// The chosen MV shows up among the scanned tables
+    pub read_chosen_mv: bool,
+    /// The raw events data (Parquet/CSV behind the `events` view) was scanned
+    pub read_raw_events: bool,
+}
+
+/// Whole-workload view of the per-query profiles
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkloadProfileSummary {
+    pub queries: Vec<ProfileSummary>,
+    pub total_latency_ms: f64,
+    pub queries_on_mv: usize,
+    pub queries_on_raw_events: usize,
+    /// Time spent per operator type across every query, slowest first
+    pub operator_totals_ms: Vec<(String, f64)>,
+    pub total_rows_scanned: u64,
+}
+
+fn metric_f64(node: &Value, key: &str) -> f64 {
+    node.get(key).and_then(Value::as_f64).unwrap_or(0.0)
+}
+
+fn metric_u64(node: &Value, key: &str) -> u64 {
+    node.get(key).and_then(Value::as_u64).unwrap_or(0)
+}
+
+fn extra_info<'a>(node: &'a Value, key: &str) -> Option<&'a str> {
+    node.get("extra_info")?.get(key)?.as_str()
+}
+
+fn operator_name(node: &Value) -> String {
+    node.get("operator_name")
+        .or_else(|| node.get("operator_type"))
+        .and_then(Value::as_str)
+        .unwrap_or("UNKNOWN")
+        .trim()
+        .to_string()
+}
+
+/// Raw events are read through table functions over the input files, never a base table
+fn is_raw_events_source(source: &str) -> bool {
+    let source = source.to_ascii_uppercase();
+    source == "EVENTS" || source.starts_with("READ_PARQUET") || source.starts_with("READ_CSV")
+}
+
+fn collect_operators(node: &Value, operators: &mut Vec<OperatorSummary>, scans: &mut Vec<ScanSummary>) {
+    if node.get("operator_name").is_some() || node.get("operator_type").is_some() {
+        let name = operator_name(node);
+        let timing_ms = metric_f64(node, "operator_timing") * 1000.0;
+        let cardinality = metric_u64(node, "operator_cardinality");
+
+        let scan_source = extra_info(node, "Table")
+            .or_else(|| extra_info(node, "Function"))
+            .map(str::to_string)
+            .or_else(|| (name.contains("SCAN") || name.starts_with("READ_")).then(|| name.clone()));
+        if let Some(source) = scan_source {
+            scans.push(ScanSummary {
+                source,
+                rows_scanned: metric_u64(node, "operator_rows_scanned"),
+                cardinality,
+                timing_ms,
+            });
+        }
+
+        operators.push(OperatorSummary {
+            name,
+            timing_ms,
+            cardinality,
+        });
+    }
+
+    if let Some(children) = node.get("children").and_then(Value::as_array) {
+        for child in children {
+            collect_operators(child, operators, scans);
+        }
+    }
+}
+
+/// Summarize one DuckDB JSON profile
+pub fn summarize_profile(query: usize, profile: &Value, chosen_mv: Option<&str>) -> ProfileSummary {
+    let mut operators = Vec::new();
+    let mut scans = Vec::new();
+    collect_operators(profile, &mut operators, &mut scans);
+
+    // Older profiles have no top-level latency; fall back to summed operator time
+    let latency_ms = match profile.get("latency").and_then(Value::as_f64) {
+        Some(latency) => latency * 1000.0,
+        None => operators.iter().map(|o| o.timing_ms).sum(),
+    };
+
+    operators.sort_by(|a, b| b.timing_ms.total_cmp(&a.timing_ms));
+    operators.truncate(TOP_OPERATORS);
+
+    let read_chosen_mv = chosen_mv.is_some_and(|mv| scans.iter().any(|s| s.source.eq_ignore_ascii_case(mv)));
+    let read_raw_events = scans.iter().any(|s| is_raw_events_source(&s.source));
+
+    ProfileSummary {
+        query,
+        chosen_mv: chosen_mv.map(str::to_string),
+        latency_ms,
+        rows_returned: metric_u64(profile, "rows_returned"),
+        top_operators: operators,
+        scans,
+        read_chosen_mv,
+        read_raw_events,
+    }
+}
+
+impl ProfileSummary {
+    pub fn print(&self) {
+        let source = match (&self.chosen_mv, self.read_chosen_mv, self.read_raw_events) {
+            (Some(mv), true, false) => format!("read MV {}", mv),
+            (Some(mv), _, true) => format!("planned for MV {} but read raw events", mv),
+            (Some(mv), false, false) => format!("planned for MV {} (not seen in scans)", mv),
+            (None, _, _) => "read raw events".to_string(),
+        };
+        println!(
+            "Q{} profile: {:.2} ms, {} rows returned, {}",
+            self.query, self.latency_ms, self.rows_returned, source
+        );
+        for scan in &self.scans {
+            println!(
+                "    scan {}: {} rows scanned -> {} rows ({:.2} ms)",
+                scan.source, scan.rows_scanned, scan.cardinality, scan.timing_ms
+            );
+        }
+        let top: Vec<String> = self
+            .top_operators
+            .iter()
+            .map(|o| format!("{} {:.2} ms ({} rows)", o.name, o.timing_ms, o.cardinality))
+            .collect();
+        println!("    top operators: {}", top.join(", "));
+    }
+}
+
+impl WorkloadProfileSummary {
+    /// Operator totals are computed from every operator, not just each query's top ones
+    pub fn from_profiles(profiles: &[(usize, Value, Option<String>)]) -> Self {
+        let mut operator_totals: HashMap<String, f64> = HashMap::new();
+        let mut queries = Vec::new();
+
+        for (query, profile, chosen_mv) in profiles {
+            let mut operators = Vec::new();
+            let mut scans = Vec::new();
+            collect_operators(profile, &mut operators, &mut scans);
+            for op in operators {
+                *operator_totals.entry(op.name).or_insert(0.0) += op.timing_ms;
+            }
+            queries.push(summarize_profile(*query, profile, chosen_mv.as_deref()));
+        }
+
+        let mut operator_totals_ms: Vec<(String, f64)> = operator_totals.into_iter().collect();
+        operator_totals_ms.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        Self {
+            total_latency_ms: queries.iter().map(|q| q.latency_ms).sum(),
+            queries_on_mv: queries.iter().filter(|q| q.read_chosen_mv && !q.read_raw_events).count(),
+            queries_on_raw_events: queries.iter().filter(|q| q.read_raw_events).count(),
+            total_rows_scanned: queries.iter().flat_map(|q| &q.scans).map(|s| s.rows_scanned).sum(),
+            operator_totals_ms,
+            queries,
+        }
+    }
+
+    pub fn print(&self) {
+        for query in &self.queries {
+            query.print();
+        }
+        println!(
+            "Profile summary: {:.2} ms total, {} queries on MVs, {} on raw events, {} rows scanned",
+            self.total_latency_ms, self.queries_on_mv, self.queries_on_raw_events, self.total_rows_scanned
+        );
+        let top: Vec<String> = self
+            .operator_totals_ms
+            .iter()
+            .take(TOP_OPERATORS)
+            .map(|(name, ms)| format!("{} {:.2} ms", name, ms))
+            .collect();
+        println!("    slowest operators overall: {}", top.join(", "));
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
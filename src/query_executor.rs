@@ -1,58 +1,19 @@
 use anyhow::Result;
 use duckdb::Connection;
-use std::path::PathBuf;
+use indicatif::ProgressBar;
+use std::path::{Path, PathBuf};
 use std::fs;
+use std::io::Write;
+use std::time::{Duration, Instant};
 
-fn extract_value_as_string(row: &duckdb::Row, col_index: usize) -> String {
-    let value = row.get_ref::<usize>(col_index).unwrap();
-    match value {
-        duckdb::types::ValueRef::Null => String::from("NULL"),
-        duckdb::types::ValueRef::Boolean(b) => b.to_string(),
-        duckdb::types::ValueRef::TinyInt(i) => i.to_string(),
-        duckdb::types::ValueRef::SmallInt(i) => i.to_string(),
-        duckdb::types::ValueRef::Int(i) => i.to_string(),
-        duckdb::types::ValueRef::BigInt(i) => i.to_string(),
-        duckdb::types::ValueRef::HugeInt(i) => i.to_string(),
-        duckdb::types::ValueRef::UTinyInt(u) => u.to_string(),
-        duckdb::types::ValueRef::USmallInt(u) => u.to_string(),
-        duckdb::types::ValueRef::UInt(u) => u.to_string(),
-        duckdb::types::ValueRef::UBigInt(u) => u.to_string(),
-        duckdb::types::ValueRef::Float(f) => trim_float(f as f64),
-        duckdb::types::ValueRef::Double(d) => trim_float(d),
-        duckdb::types::ValueRef::Decimal(d) => d.to_string(),
-        duckdb::types::ValueRef::Timestamp(_, ts) => format!("{}", ts),
-        duckdb::types::ValueRef::Text(bytes) => {
-            match std::str::from_utf8(bytes) {
-                Ok(s) => s.to_string(),
-                Err(_) => format!("{:?}", bytes),
-            }
-        },
-        duckdb::types::ValueRef::Blob(bytes) => format!("{:?}", bytes),
-        duckdb::types::ValueRef::Date32(i) => {
-            use chrono::{NaiveDate, Datelike};
-            let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
-            if let Some(date) = epoch.checked_add_signed(chrono::Duration::days(i as i64)) {
-                format!("{:04}-{:02}-{:02}", date.year(), date.month(), date.day())
-            } else {
-                i.to_string()
-            }
-        },
-        duckdb::types::ValueRef::Time64(_, i) => i.to_string(),
-        duckdb::types::ValueRef::Interval { months, days, nanos } => format!("{}-{}-{}", months, days, nanos),
-        _ => "<unsupported>".to_string(),
-    }
-}
-
-fn trim_float(v: f64) -> String {
-    let s = v.to_string();
-    if s.contains('.') {
-        s.trim_end_matches('0').trim_end_matches('.').to_string()
-    } else {
-        s
-    }
-}
+use crate::benchmark::TimingMode;
+use crate::hardware::DuckDbSettings;
+use crate::result_writer::{OutputFormat, normalize_column_name, write_csv_records};
+use crate::watchdog::QueryWatchdog;
 
-pub fn explain_query(con: &Connection, sql: &str, query_num: usize) -> Result<()> {
+/// Run the query once with JSON profiling enabled and save the profile to `profiling/qN.json`.
+/// Returns the parsed profile, or None (with a warning) when DuckDB produced nothing readable.
+pub fn explain_query(con: &Connection, sql: &str, query_num: usize, pb: &ProgressBar) -> Result<Option<serde_json::Value>> {
     use std::path::PathBuf;
     
     let profile_dir = PathBuf::from("profiling");
@@ -64,25 +25,34 @@ pub fn explain_query(con: &Connection, sql: &str, query_num: usize) -> Result<()
     con.execute(&format!("PRAGMA profiling_output = '{}'", temp_file), [])?;
     
     con.execute(
-        r#"PRAGMA custom_profiling_settings = '{"OPERATOR_TIMING": "true", "OPERATOR_CARDINALITY": "true", "CPU_TIME": "true", "EXTRA_INFO": "true"}'"#,
+        r#"PRAGMA custom_profiling_settings = '{"OPERATOR_TIMING": "true", "OPERATOR_CARDINALITY": "true", "OPERATOR_ROWS_SCANNED": "true", "CPU_TIME": "true", "LATENCY": "true", "ROWS_RETURNED": "true", "EXTRA_INFO": "true"}'"#,
         [],
     )?;
     
-    let mut stmt = con.prepare(sql)?;
-    let _rows = stmt.query([])?;
-    
+    {
+        let mut stmt = con.prepare(sql)?;
+        let _rows = stmt.query([])?;
+    }
+    // Profiling is per connection; leave it off so timed runs are not profiled too
+    con.execute("PRAGMA disable_profiling", [])?;
     
-    match std::fs::read_to_string(&temp_file) {
-        Ok(json_content) => {
-            std::fs::write(&profile_file, &json_content)?;
-            
-            let _ = std::fs::remove_file(&temp_file);
+    let json_content = match std::fs::read_to_string(&temp_file) {
+        Ok(json_content) => json_content,
+        Err(e) => {
+            pb.println(format!("Warning: no profile written for Q{}: {}", query_num, e));
+            return Ok(None);
         }
-        Err(_e) => {
+    };
+    std::fs::write(&profile_file, &json_content)?;
+    let _ = std::fs::remove_file(&temp_file);
+
+    match serde_json::from_str(&json_content) {
+        Ok(profile) => Ok(Some(profile)),
+        Err(e) => {
+            pb.println(format!("Warning: could not parse profile {}: {}", profile_file.display(), e));
+            Ok(None)
         }
     }
-    
-    Ok(())
 }
 
 pub fn prepare_query<'a>(con: &'a Connection, sql: &str) -> Result<duckdb::Statement<'a>> {
@@ -90,44 +60,257 @@ pub fn prepare_query<'a>(con: &'a Connection, sql: &str) -> Result<duckdb::State
     Ok(stmt)
 }
 
-pub fn write_single_result_to_csv(
+pub fn write_single_result(
     query_num: usize,
-    mut rows: duckdb::Rows,
+    rows: duckdb::Rows,
     output_dir: &PathBuf,
+    format: OutputFormat,
 ) -> Result<()> {
     fs::create_dir_all(output_dir)?;
     
-    let out_path = output_dir.join(format!("q{}.csv", query_num));
-    let mut file = std::fs::File::create(&out_path)?;
-    let mut wtr = csv::Writer::from_writer(&mut file);
-    
+    let out_path = output_dir.join(format!("q{}.{}", query_num, format.extension()));
+    format.writer().write(rows, &out_path)
+}
+
+/// Write reference results for every query by running its plain SQL (`assemble_sql`) directly
+/// on the raw `events` data, bypassing the planner and every MV. These are the ground truth
+/// that MV-routed plans are checked against with `--baseline-dir`.
+pub fn generate_baseline(con: &Connection, queries: &[serde_json::Value], baseline_dir: &PathBuf, pb: &ProgressBar) -> Result<()> {
+    for (i, query) in queries.iter().enumerate() {
+        let sql = crate::query_handler::assemble_sql(query);
+        let mut stmt = prepare_query(con, &sql)
+            .map_err(|e| anyhow::anyhow!("Q{}: failed to prepare raw SQL `{}`: {}", i + 1, sql, e))?;
+        let rows = stmt.query([])?;
+        write_single_result(i + 1, rows, baseline_dir, OutputFormat::Csv)?;
+        pb.inc(1);
+    }
+    Ok(())
+}
+
+/// Time spent producing one query result, split by phase, in seconds
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PhaseTimes {
+    pub execute: f64,
+    pub fetch: f64,
+    pub serialize: f64,
+    pub rows: usize,
+    /// The query was interrupted by a timeout (or never started because the global deadline passed)
+    pub timed_out: bool,
+}
+
+impl PhaseTimes {
+    pub fn total(&self) -> f64 {
+        self.execute + self.fetch + self.serialize
+    }
+}
+
+/// A fully drained and decoded query result
+pub struct DecodedResult {
+    pub columns: Vec<String>,
+    pub records: Vec<Vec<duckdb::types::Value>>,
+}
+
+impl DecodedResult {
+    pub fn write_csv(&self, query_num: usize, output_dir: &PathBuf) -> Result<()> {
+        fs::create_dir_all(output_dir)?;
+        let mut file = fs::File::create(output_dir.join(format!("q{}.csv", query_num)))?;
+        write_csv_records(&self.columns, &self.records, &mut file)
+    }
+}
+
+/// Execute a prepared statement and time every phase up to the last serialized byte:
+/// `query()`, draining and decoding every row into owned values, and CSV encoding into `out`.
+pub fn run_end_to_end(stmt: &mut duckdb::Statement, out: &mut dyn Write) -> Result<(PhaseTimes, DecodedResult)> {
+    let execute_start = Instant::now();
+    let mut rows = stmt.query([])?;
+    let execute = execute_start.elapsed().as_secs_f64();
+
+    let fetch_start = Instant::now();
     let stmt_ref = rows.as_ref().ok_or_else(|| anyhow::anyhow!("Failed to get statement reference"))?;
     let column_count = stmt_ref.column_count();
-    let mut columns: Vec<String> = (0..column_count)
-        .map(|i| stmt_ref.column_name(i).map(|s| s.to_string()))
+    let columns: Vec<String> = (0..column_count)
+        .map(|i| stmt_ref.column_name(i).map(|s| normalize_column_name(s)))
         .collect::<std::result::Result<Vec<_>, _>>()?;
-    
-    // Fix column name normalization: DuckDB Rust bindings may return count(*) instead of count_star()
-    // Check if we have COUNT(*) and normalize it
-    for col in &mut columns {
-        if col == "count(*)" {
-            *col = "count_star()".to_string();
+    let mut records = Vec::new();
+    while let Some(row) = rows.next()? {
+        let record = (0..column_count)
+            .map(|i| row.get::<_, duckdb::types::Value>(i))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        records.push(record);
+    }
+    let fetch = fetch_start.elapsed().as_secs_f64();
+
+    let serialize_start = Instant::now();
+    write_csv_records(&columns, &records, out)?;
+    let serialize = serialize_start.elapsed().as_secs_f64();
+
+    let times = PhaseTimes { execute, fetch, serialize, rows: records.len(), timed_out: false };
+    Ok((times, DecodedResult { columns, records }))
+}
+
+/// Run one query end to end. The first run serializes into the query's result file (unless
+/// `discard`); later runs, and discarded first runs, serialize into a discard sink so parallel
+/// workers never write the same file. Discarded first-run results are still written out
+/// afterwards, untimed.
+pub fn run_end_to_end_to_output(
+    stmt: &mut duckdb::Statement,
+    query_num: usize,
+    first_run: bool,
+    discard: bool,
+    output_dir: &PathBuf,
+) -> Result<PhaseTimes> {
+    if first_run && !discard {
+        fs::create_dir_all(output_dir)?;
+        let mut file = fs::File::create(output_dir.join(format!("q{}.csv", query_num)))?;
+        let (times, _) = run_end_to_end(stmt, &mut file)?;
+        Ok(times)
+    } else {
+        let (times, decoded) = run_end_to_end(stmt, &mut std::io::sink())?;
+        if first_run {
+            decoded.write_csv(query_num, output_dir)?;
         }
+        Ok(times)
     }
-    
-    wtr.write_record(&columns)?;
-    
-    while let Some(row) = rows.next()? {
-        let mut record = Vec::new();
-        for i in 0..column_count {
-            let value = extract_value_as_string(&row, i);
-            record.push(value);
+}
+
+/// Drop the OS page cache so the next read of the database file goes to disk.
+/// Only possible on Linux, and only when running as root.
+pub fn drop_os_page_cache() -> Result<()> {
+    if !cfg!(target_os = "linux") {
+        anyhow::bail!("dropping the page cache is only supported on Linux");
+    }
+    std::process::Command::new("sync").status()?;
+    fs::write("/proc/sys/vm/drop_caches", "3")?;
+    Ok(())
+}
+
+/// Settings shared by the serial, cold and parallel runners
+pub struct RunOptions<'a> {
+    pub runs: usize,
+    pub output_dir: &'a PathBuf,
+    pub format: OutputFormat,
+    pub timing: TimingMode,
+    pub discard_output: bool,
+    /// Longest a single execution may run before it is interrupted
+    pub query_timeout: Option<Duration>,
+    /// Point after which remaining executions are interrupted or skipped
+    pub global_deadline: Option<Instant>,
+    /// Applied to every fresh connection opened by cold and first-run modes
+    pub duckdb_settings: &'a DuckDbSettings,
+}
+
+impl RunOptions<'_> {
+    pub fn has_timeout(&self) -> bool {
+        self.query_timeout.is_some() || self.global_deadline.is_some()
+    }
+
+    /// Timeout for the next execution: the per-query timeout capped by what is left of the
+    /// global budget. `Some(Duration::ZERO)` means the global deadline has already passed.
+    fn next_timeout(&self) -> Option<Duration> {
+        let remaining = self.global_deadline.map(|d| d.saturating_duration_since(Instant::now()));
+        match (self.query_timeout, remaining) {
+            (Some(q), Some(r)) => Some(q.min(r)),
+            (q, r) => q.or(r),
         }
-        wtr.write_record(&record)?;
     }
-    
-    wtr.flush()?;
-    
+}
+
+/// Record a first-run timeout in the output directory in place of the (possibly partial) result file
+fn write_timeout_marker(query_num: usize, opts: &RunOptions, elapsed: Duration) -> Result<()> {
+    let output_dir = opts.output_dir;
+    fs::create_dir_all(output_dir)?;
+    let _ = fs::remove_file(output_dir.join(format!("q{}.{}", query_num, opts.format.extension())));
+    fs::write(
+        output_dir.join(format!("q{}.timeout", query_num)),
+        format!("Timed out after {:.3}s\n", elapsed.as_secs_f64()),
+    )?;
     Ok(())
 }
 
+/// Execute one prepared query under the configured timing mode. On the first run the
+/// result is also written to the output directory. With a timeout configured, the query is
+/// interrupted through `watchdog` when it runs too long and reported as timed out rather
+/// than failing the whole workload.
+pub fn execute_timed(
+    stmt: &mut duckdb::Statement,
+    query_num: usize,
+    first_run: bool,
+    opts: &RunOptions,
+    watchdog: Option<&QueryWatchdog>,
+) -> Result<PhaseTimes> {
+    let timeout = opts.next_timeout();
+    if timeout.is_some_and(|t| t.is_zero()) {
+        if first_run {
+            write_timeout_marker(query_num, opts, Duration::ZERO)?;
+        }
+        return Ok(PhaseTimes { timed_out: true, ..Default::default() });
+    }
+    if let (Some(timeout), Some(watchdog)) = (timeout, watchdog) {
+        watchdog.arm(timeout);
+    }
+
+    let start = Instant::now();
+    let result = match opts.timing {
+        TimingMode::Execute => {
+            let query_start = Instant::now();
+            stmt.query([]).map_err(anyhow::Error::from).and_then(|rows| {
+                let execute = query_start.elapsed().as_secs_f64();
+                if first_run {
+                    write_single_result(query_num, rows, opts.output_dir, opts.format)?;
+                }
+                Ok(PhaseTimes { execute, ..Default::default() })
+            })
+        }
+        TimingMode::EndToEnd => run_end_to_end_to_output(stmt, query_num, first_run, opts.discard_output, opts.output_dir),
+    };
+
+    let interrupted = watchdog.is_some_and(|w| w.disarm());
+    match result {
+        Err(_) if interrupted => {
+            let elapsed = start.elapsed();
+            if first_run {
+                write_timeout_marker(query_num, opts, elapsed)?;
+            }
+            Ok(PhaseTimes { execute: elapsed.as_secs_f64(), timed_out: true, ..Default::default() })
+        }
+        other => other,
+    }
+}
+
+/// Run every query once per run, each run on a freshly opened database so DuckDB's buffer
+/// pool starts empty. Opening the database and preparing statements are not timed.
+pub fn run_queries_cold(
+    db_path: &Path,
+    sql_queries: &[String],
+    drop_caches: bool,
+    opts: &RunOptions,
+    pb: &ProgressBar,
+) -> Result<Vec<Vec<PhaseTimes>>> {
+    let mut query_times = vec![Vec::new(); sql_queries.len()];
+    let mut warned = false;
+
+    for run in 1..=opts.runs {
+        if drop_caches
+            && let Err(e) = drop_os_page_cache()
+            && !warned
+        {
+            pb.println(format!("Warning: could not drop OS page cache: {}", e));
+            warned = true;
+        }
+
+        let con = Connection::open(db_path)?;
+        opts.duckdb_settings.apply(&con)?;
+        let watchdog = opts.has_timeout().then(|| QueryWatchdog::new(&con));
+        let mut statements = sql_queries
+            .iter()
+            .map(|sql| prepare_query(&con, sql))
+            .collect::<Result<Vec<_>>>()?;
+
+        for (i, stmt) in statements.iter_mut().enumerate() {
+            query_times[i].push(execute_timed(stmt, i + 1, run == 1, opts, watchdog.as_ref())?);
+        }
+        pb.inc(1);
+    }
+
+    Ok(query_times)
+}
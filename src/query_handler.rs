@@ -9,6 +9,30 @@ pub fn parse_queries_from_file(queries_path: &PathBuf) -> Result<Vec<Value>> {
     Ok(queries)
 }
 
+/// Collect the column pairs that appear together in a WHERE clause anywhere in the workload.
+/// Each pair is ordered `(a, b)` with `a < b` so it can be used as a stats key.
+pub fn predicate_column_pairs(queries: &[Value]) -> Vec<(String, String)> {
+    let mut pairs = Vec::new();
+    for q in queries {
+        let mut cols: Vec<&str> = q.get("where")
+            .and_then(|w| w.as_array())
+            .map(|arr| arr.iter().filter_map(|c| c.get("col").and_then(|v| v.as_str())).collect())
+            .unwrap_or_default();
+        cols.sort();
+        cols.dedup();
+
+        for (i, a) in cols.iter().enumerate() {
+            for b in &cols[i + 1..] {
+                let pair = (a.to_string(), b.to_string());
+                if !pairs.contains(&pair) {
+                    pairs.push(pair);
+                }
+            }
+        }
+    }
+    pairs
+}
+
 pub fn assemble_sql(q: &Value) -> String {
     let select = select_to_sql(q.get("select").unwrap_or(&Value::Array(vec![])));
     let from_tbl = q["from"].as_str().unwrap_or("events_table");
@@ -88,7 +112,7 @@ fn where_to_sql(where_clause: Option<&Value>) -> String {
     }
 }
 
-fn format_value_for_sql(val: &serde_json::Value) -> String {
+pub fn format_value_for_sql(val: &serde_json::Value) -> String {
     if let Some(num) = val.as_f64() {
         if num.fract() == 0.0 {
             format!("{}", num as i64)
@@ -1,8 +1,124 @@
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::fs;
 
-pub fn compare_results(baseline_dir: &Path, output_dir: &Path) -> Result<()> {
+/// How far apart two numeric cells may be and still match. A pair matches when it is within
+/// `abs`, within `rel` of the larger magnitude, or at most `ulps` representable doubles apart.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Tolerance {
+    #[serde(default)]
+    pub abs: f64,
+    #[serde(default)]
+    pub rel: f64,
+    #[serde(default)]
+    pub ulps: u64,
+}
+
+impl Tolerance {
+    pub const EXACT: Tolerance = Tolerance { abs: 0.0, rel: 0.0, ulps: 0 };
+
+    fn matches(&self, cell1: &str, cell2: &str) -> bool {
+        if cell1 == cell2 {
+            return true;
+        }
+        
+        // Integers (counts, ids) compare exactly, even beyond f64 precision
+        if *self == Tolerance::EXACT
+            && let (Ok(int1), Ok(int2)) = (cell1.parse::<i128>(), cell2.parse::<i128>())
+        {
+            return int1 == int2;
+        }
+        
+        let (Ok(val1), Ok(val2)) = (cell1.parse::<f64>(), cell2.parse::<f64>()) else {
+            return false;
+        };
+        if val1 == val2 {
+            return true;
+        }
+        let diff = (val1 - val2).abs();
+        diff <= self.abs || diff <= self.rel * val1.abs().max(val2.abs()) || ulp_distance(val1, val2) <= self.ulps
+    }
+}
+
+/// Number of representable doubles between `a` and `b`
+fn ulp_distance(a: f64, b: f64) -> u64 {
+    if a.is_nan() || b.is_nan() {
+        return u64::MAX;
+    }
+    // Map the sign-magnitude bit pattern onto a monotonic integer line
+    let ordered = |x: f64| {
+        let bits = x.to_bits() as i64;
+        if bits < 0 { i64::MIN - bits } else { bits }
+    };
+    (ordered(a) as i128 - ordered(b) as i128).unsigned_abs().min(u64::MAX as u128) as u64
+}
+
+/// Per-column numeric tolerances for result comparison, loadable from JSON:
+///
+/// `{"default": {"rel": 1e-9}, "aggregates": {"avg": {"rel": 1e-6}}, "columns": {"sum(bid_price)": {"abs": 0.01}}}`
+///
+/// A column uses its entry in `columns`, else its aggregate's entry in `aggregates`, else
+/// `default`. Counts and group-by columns are exact unless configured explicitly.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToleranceConfig {
+    #[serde(default = "ToleranceConfig::default_tolerance")]
+    pub default: Tolerance,
+    /// Keyed by aggregate function: sum, avg, min, max, count
+    #[serde(default)]
+    pub aggregates: HashMap<String, Tolerance>,
+    /// Keyed by result column name as it appears in the CSV header
+    #[serde(default)]
+    pub columns: HashMap<String, Tolerance>,
+}
+
+impl Default for ToleranceConfig {
+    fn default() -> Self {
+        Self {
+            default: Self::default_tolerance(),
+            aggregates: HashMap::new(),
+            columns: HashMap::new(),
+        }
+    }
+}
+
+impl ToleranceConfig {
+    /// The checker's original absolute 0.1, so existing baselines keep passing; `rel` and
+    /// `ulps` only add slack for parallel summation order on large values
+    fn default_tolerance() -> Tolerance {
+        Tolerance { abs: 0.1, rel: 1e-9, ulps: 4 }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read tolerance config {}: {}", path.display(), e))?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub fn for_column(&self, column: &str) -> Tolerance {
+        if let Some(tolerance) = self.columns.get(column) {
+            return *tolerance;
+        }
+        // "sum(bid_price)" -> "sum", "count_star()" -> "count"
+        let Some((func, _)) = column.split_once('(') else {
+            return Tolerance::EXACT;
+        };
+        let func = func.trim().to_ascii_lowercase();
+        let func = if func == "count_star" { "count".to_string() } else { func };
+        match self.aggregates.get(&func) {
+            Some(tolerance) => *tolerance,
+            None if func == "count" => Tolerance::EXACT,
+            None => self.default,
+        }
+    }
+}
+
+/// Compare every `qN.csv` in `baseline_dir` against `output_dir`. When `queries` holds the
+/// workload definitions, row order is enforced for queries with `order_by` (see
+/// `compare_csv_files`); otherwise results are compared as unordered multisets.
+pub fn compare_results(baseline_dir: &Path, output_dir: &Path, queries: &[Value], tolerances: &ToleranceConfig) -> Result<()> {
     let baseline_files = get_query_files(baseline_dir)?;
     let mut failed = Vec::new();
     
@@ -12,26 +128,147 @@ pub fn compare_results(baseline_dir: &Path, output_dir: &Path) -> Result<()> {
         let output_file = output_dir.join(format!("q{}.csv", query_num));
         
         if !output_file.exists() {
-            failed.push((query_num, "Missing output file".to_string()));
+            println!("Q{}: missing output file {}", query_num, output_file.display());
+            failed.push(query_num);
             continue;
         }
         
-        match compare_csv_files(baseline_file, &output_file) {
-            Ok(()) => {
+        match compare_csv_files(baseline_file, &output_file, queries.get(query_num - 1), tolerances) {
+            Ok(diff) if diff.is_match() => {}
+            Ok(diff) => {
+                diff.print(query_num);
+                failed.push(query_num);
             }
             Err(e) => {
-                failed.push((query_num, e.to_string()));
+                println!("Q{}: {}", query_num, e);
+                failed.push(query_num);
             }
         }
     }
     
     if !failed.is_empty() {
-        anyhow::bail!("Some queries failed comparison")
+        let names: Vec<String> = failed.iter().map(|q| format!("Q{}", q)).collect();
+        anyhow::bail!(
+            "{} of {} queries failed comparison: {}",
+            failed.len(),
+            baseline_files.len(),
+            names.join(", ")
+        )
     }
     
+    println!("All {} queries match the baseline", baseline_files.len());
     Ok(())
 }
 
+/// How many missing/extra rows and deltas to print per query
+const MAX_SHOWN: usize = 5;
+
+/// A metric that differs beyond tolerance between rows that otherwise match
+pub struct NumericDelta {
+    /// The baseline row the delta was found in
+    pub row: Vec<String>,
+    pub column: String,
+    pub baseline: f64,
+    pub output: f64,
+}
+
+/// Everything that differs between a baseline result and our output for one query
+#[derive(Default)]
+pub struct QueryDiff {
+    /// Baseline and output headers, when they differ
+    pub header: Option<(Vec<String>, Vec<String>)>,
+    /// Baseline rows with no counterpart in the output
+    pub missing_rows: Vec<Vec<String>>,
+    /// Output rows with no counterpart in the baseline
+    pub extra_rows: Vec<Vec<String>>,
+    pub numeric_deltas: Vec<NumericDelta>,
+    /// First position where the output breaks the query's ORDER BY
+    pub order_violation: Option<String>,
+}
+
+impl QueryDiff {
+    pub fn is_match(&self) -> bool {
+        self.header.is_none()
+            && self.missing_rows.is_empty()
+            && self.extra_rows.is_empty()
+            && self.numeric_deltas.is_empty()
+            && self.order_violation.is_none()
+    }
+
+    /// One-line description of the mismatch
+    pub fn summary(&self) -> String {
+        if let Some((baseline, output)) = &self.header {
+            return format!("header [{}] vs [{}]", baseline.join(", "), output.join(", "));
+        }
+        let mut parts = Vec::new();
+        if !self.missing_rows.is_empty() {
+            parts.push(format!("{} missing rows", self.missing_rows.len()));
+        }
+        if !self.extra_rows.is_empty() {
+            parts.push(format!("{} extra rows", self.extra_rows.len()));
+        }
+        if !self.numeric_deltas.is_empty() {
+            parts.push(format!("{} numeric deltas", self.numeric_deltas.len()));
+        }
+        if let Some(violation) = &self.order_violation {
+            parts.push(format!("order: {}", violation));
+        }
+        parts.join(", ")
+    }
+
+    pub fn print(&self, query_num: usize) {
+        println!("Q{}: mismatch", query_num);
+
+        if let Some((baseline, output)) = &self.header {
+            let missing: Vec<&String> = baseline.iter().filter(|c| !output.contains(c)).collect();
+            let extra: Vec<&String> = output.iter().filter(|c| !baseline.contains(c)).collect();
+            println!("    header: baseline [{}] vs output [{}]", baseline.join(", "), output.join(", "));
+            if !missing.is_empty() || !extra.is_empty() {
+                println!("    missing columns: {:?}, extra columns: {:?}", missing, extra);
+            } else {
+                println!("    same columns in a different order");
+            }
+        }
+
+        if let Some(violation) = &self.order_violation {
+            println!("    order: {}", violation);
+        }
+
+        print_rows("missing row (in baseline, not in output)", &self.missing_rows);
+        print_rows("extra row (in output, not in baseline)", &self.extra_rows);
+
+        if !self.numeric_deltas.is_empty() {
+            println!("    {} numeric deltas:", self.numeric_deltas.len());
+            for delta in self.numeric_deltas.iter().take(MAX_SHOWN) {
+                println!(
+                    "      [{}] {}: baseline {} output {} (delta {:+})",
+                    delta.row.join(", "),
+                    delta.column,
+                    delta.baseline,
+                    delta.output,
+                    delta.output - delta.baseline
+                );
+            }
+            if self.numeric_deltas.len() > MAX_SHOWN {
+                println!("      ... {} more", self.numeric_deltas.len() - MAX_SHOWN);
+            }
+        }
+    }
+}
+
+fn print_rows(label: &str, rows: &[Vec<String>]) {
+    if rows.is_empty() {
+        return;
+    }
+    println!("    {} {}{}:", rows.len(), label, if rows.len() == 1 { "" } else { "s" });
+    for row in rows.iter().take(MAX_SHOWN) {
+        println!("      [{}]", row.join(", "));
+    }
+    if rows.len() > MAX_SHOWN {
+        println!("      ... {} more", rows.len() - MAX_SHOWN);
+    }
+}
+
 fn get_query_files(dir: &Path) -> Result<Vec<PathBuf>> {
     let entries = fs::read_dir(dir)?;
     let mut files: Vec<PathBuf> = Vec::new();
@@ -69,84 +306,314 @@ fn extract_query_number(path: &Path) -> usize {
     0
 }
 
-fn compare_csv_files(baseline_file: &Path, output_file: &Path) -> Result<()> {
+/// Locate a query's column in a result header, tolerating case and the `count(*)` naming
+fn column_index(header: &[String], col: &str) -> Option<usize> {
+    let normalize = |name: &str| {
+        let name = name.to_ascii_lowercase().replace(' ', "");
+        if name == "count(*)" { "count_star()".to_string() } else { name }
+    };
+    let col = normalize(col);
+    header.iter().position(|h| normalize(h) == col)
+}
+
+/// Compare a query's result against its baseline.
+///
+/// Rows are matched as a multiset. If the query has an `order_by`, the ORDER BY key of every
+/// output row must also equal the key of the baseline row at the same position, so rows may
+/// only be permuted among ties. Under a LIMIT that the baseline fills, the trailing tie group
+/// is cut arbitrarily by the engine: those rows only need the boundary key, not the same
+/// identity. A LIMIT without ORDER BY can return any subset, so only the row count is checked.
+/// An ORDER BY column missing from the header is reported as an order violation.
+pub fn compare_csv_files(
+    baseline_file: &Path,
+    output_file: &Path,
+    query: Option<&Value>,
+    tolerance_config: &ToleranceConfig,
+) -> Result<QueryDiff> {
     let (baseline_header, baseline_rows) = parse_csv(baseline_file)?;
     let (output_header, output_rows) = parse_csv(output_file)?;
     
     if baseline_header != output_header {
-        anyhow::bail!("Headers don't match");
+        return Ok(QueryDiff {
+            header: Some((baseline_header, output_header)),
+            ..Default::default()
+        });
     }
     
-    if baseline_rows.len() != output_rows.len() {
-        anyhow::bail!("Row count mismatch (baseline: {}, output: {})", 
-                     baseline_rows.len(), output_rows.len());
+    let tolerances: Vec<Tolerance> = baseline_header.iter().map(|h| tolerance_config.for_column(h)).collect();
+    
+    // Rows are keyed by the query's group-by columns, or by every non-aggregate column
+    let group_by: Option<Vec<usize>> = query
+        .and_then(|q| q.get("group_by"))
+        .and_then(Value::as_array)
+        .and_then(|arr| arr.iter().map(|c| c.as_str().and_then(|c| column_index(&baseline_header, c))).collect());
+    let match_columns: Vec<usize> = group_by.unwrap_or_else(|| {
+        (0..baseline_header.len()).filter(|&i| !baseline_header[i].contains('(')).collect()
+    });
+    
+    let limit = query.and_then(|q| q.get("limit")).and_then(Value::as_u64).map(|l| l as usize);
+    let order_by: Vec<&str> = query
+        .and_then(|q| q.get("order_by"))
+        .and_then(Value::as_array)
+        .map(|arr| arr.iter().filter_map(|o| o.get("col").and_then(Value::as_str)).collect())
+        .unwrap_or_default();
+    let key_columns: Option<Vec<usize>> = order_by.iter().map(|col| column_index(&baseline_header, col)).collect();
+    let key_columns = key_columns.filter(|cols| !cols.is_empty());
+    let limit_reached = limit.is_some_and(|l| baseline_rows.len() == l);
+    
+    if order_by.is_empty() && limit_reached {
+        let mut diff = QueryDiff::default();
+        if output_rows.len() != baseline_rows.len() {
+            diff.order_violation = Some(format!(
+                "LIMIT {} without ORDER BY: expected {} rows, got {}",
+                limit.unwrap_or(0),
+                baseline_rows.len(),
+                output_rows.len()
+            ));
+        }
+        return Ok(diff);
+    }
+    
+    let Some(key_columns) = key_columns else {
+        let mut diff = diff_multiset(&baseline_header, &tolerances, &match_columns, &baseline_rows, &output_rows);
+        // An ORDER BY the result cannot be checked against is a mismatch, not an unordered pass
+        let unresolved: Vec<&str> = order_by
+            .iter()
+            .copied()
+            .filter(|col| column_index(&baseline_header, col).is_none())
+            .collect();
+        if !unresolved.is_empty() {
+            diff.order_violation = Some(format!(
+                "ORDER BY column {} not in the result header, so row order cannot be checked",
+                unresolved.join(", ")
+            ));
+        }
+        return Ok(diff);
+    };
+    
+    let keys_match = |a: &[String], b: &[String]| key_columns.iter().all(|&c| tolerances[c].matches(&a[c], &b[c]));
+    
+    // Rows tied with the last baseline row at a filled LIMIT are interchangeable
+    let boundary_start = |rows: &[Vec<String>]| match (limit_reached, baseline_rows.last()) {
+        (true, Some(last)) => rows.len() - rows.iter().rev().take_while(|r| keys_match(r, last)).count(),
+        _ => rows.len(),
+    };
+    let baseline_body = &baseline_rows[..boundary_start(&baseline_rows)];
+    let output_body = &output_rows[..boundary_start(&output_rows)];
+    
+    let mut diff = diff_multiset(&baseline_header, &tolerances, &match_columns, baseline_body, output_body);
+    
+    let first_out_of_order = baseline_rows
+        .iter()
+        .zip(&output_rows)
+        .position(|(b, o)| !keys_match(b, o));
+    if let Some(row) = first_out_of_order {
+        let describe = |r: &[String]| {
+            key_columns
+                .iter()
+                .map(|&c| format!("{}={}", baseline_header[c], r[c]))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        diff.order_violation = Some(format!(
+            "row {} expected {} but got {}",
+            row + 1,
+            describe(&baseline_rows[row]),
+            describe(&output_rows[row])
+        ));
+    } else if limit_reached && baseline_rows.len() != output_rows.len() {
+        // Without a limit, a length difference already shows up as missing or extra rows
+        diff.order_violation = Some(format!(
+            "LIMIT {}: expected {} rows, got {}",
+            limit.unwrap_or(0),
+            baseline_rows.len(),
+            output_rows.len()
+        ));
+    }
+    
+    Ok(diff)
+}
+
+/// Canonical form of a key cell so "7", "7.0" and "7.00" hash the same
+fn normalize_key_cell(cell: &str) -> String {
+    if let Ok(int) = cell.parse::<i128>() {
+        int.to_string()
+    } else if let Ok(float) = cell.parse::<f64>() {
+        if float.fract() == 0.0 && float.abs() < 1e15 {
+            (float as i128).to_string()
+        } else {
+            float.to_string()
+        }
+    } else {
+        cell.to_string()
+    }
+}
+
+/// Order rows cell by cell, numbers numerically and before text, so rows that match within
+/// tolerance end up next to each other
+fn compare_rows(a: &[String], b: &[String]) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+    for (x, y) in a.iter().zip(b) {
+        let ord = match (x.parse::<f64>(), y.parse::<f64>()) {
+            (Ok(x), Ok(y)) => x.total_cmp(&y),
+            (Ok(_), Err(_)) => Ordering::Less,
+            (Err(_), Ok(_)) => Ordering::Greater,
+            (Err(_), Err(_)) => x.cmp(y),
+        };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+/// Match rows regardless of order. This is not linear: bucketing is O(n) expected, but each
+/// bucket of k rows is sorted, so the worst case (every row sharing one key) is O(n log n)
+/// row comparisons.
+///
+/// Rows are bucketed by their key columns (the group-by columns) and only compared with
+/// tolerance on the remaining metric columns within a bucket. Duplicate keys share a bucket,
+/// whose baseline and output rows are sorted and merged so each row is paired at most once.
+/// Output rows left unpaired in a bucket are matched in order with the bucket's unpaired
+/// baseline rows and reported as numeric deltas; rows with unknown keys are extra, and unused
+/// baseline rows are missing.
+fn diff_multiset(
+    header: &[String],
+    tolerances: &[Tolerance],
+    key_columns: &[usize],
+    baseline_rows: &[Vec<String>],
+    output_rows: &[Vec<String>],
+) -> QueryDiff {
+    let key_of = |row: &[String]| -> Vec<String> {
+        key_columns
+            .iter()
+            .map(|&c| row.get(c).map(|cell| normalize_key_cell(cell)).unwrap_or_default())
+            .collect()
+    };
+    
+    let mut buckets: HashMap<Vec<String>, (Vec<usize>, Vec<usize>)> = HashMap::new();
+    for (i, row) in baseline_rows.iter().enumerate() {
+        buckets.entry(key_of(row)).or_default().0.push(i);
+    }
+    let mut extra = Vec::new();
+    for (j, row) in output_rows.iter().enumerate() {
+        match buckets.get_mut(&key_of(row)) {
+            Some((_, outputs)) => outputs.push(j),
+            None => extra.push(j),
+        }
     }
     
     let mut baseline_used = vec![false; baseline_rows.len()];
+    // (output row, baseline row) pairs whose metrics differ
+    let mut mismatched = Vec::new();
     
-    for output_row in &output_rows {
-        let mut found = false;
-        for (i, baseline_row) in baseline_rows.iter().enumerate() {
-            if !baseline_used[i] && rows_match_with_tolerance(baseline_row, output_row) {
-                baseline_used[i] = true;
-                found = true;
-                break;
+    for (baselines, outputs) in buckets.values_mut() {
+        if outputs.is_empty() {
+            continue;
+        }
+        baselines.sort_by(|&a, &b| compare_rows(&baseline_rows[a], &baseline_rows[b]));
+        outputs.sort_by(|&a, &b| compare_rows(&output_rows[a], &output_rows[b]));
+        
+        let (mut unpaired_baselines, mut unpaired_outputs) = (Vec::new(), Vec::new());
+        let (mut i, mut j) = (0, 0);
+        while i < baselines.len() && j < outputs.len() {
+            let (b, o) = (baselines[i], outputs[j]);
+            if rows_match_with_tolerance(&baseline_rows[b], &output_rows[o], tolerances) {
+                baseline_used[b] = true;
+                i += 1;
+                j += 1;
+            } else if compare_rows(&baseline_rows[b], &output_rows[o]).is_lt() {
+                unpaired_baselines.push(b);
+                i += 1;
+            } else {
+                unpaired_outputs.push(o);
+                j += 1;
             }
         }
-        if !found {
-            anyhow::bail!("Row not found in baseline or already matched (duplicate mismatch)");
+        unpaired_baselines.extend_from_slice(&baselines[i..]);
+        unpaired_outputs.extend_from_slice(&outputs[j..]);
+        
+        // Exact matches are taken first, so a duplicate key does not steal the baseline row
+        // another output row matches perfectly
+        unpaired_baselines.sort_unstable();
+        unpaired_outputs.sort_unstable();
+        for (k, &o) in unpaired_outputs.iter().enumerate() {
+            match unpaired_baselines.get(k) {
+                Some(&b) => {
+                    baseline_used[b] = true;
+                    if !rows_match_with_tolerance(&baseline_rows[b], &output_rows[o], tolerances) {
+                        mismatched.push((o, b));
+                    }
+                }
+                None => extra.push(o),
+            }
         }
     }
     
-    Ok(())
+    let mut diff = QueryDiff::default();
+    extra.sort_unstable();
+    diff.extra_rows.extend(extra.iter().map(|&j| output_rows[j].clone()));
+    
+    mismatched.sort_unstable();
+    for (o, b) in mismatched {
+        let (baseline_row, output_row) = (&baseline_rows[b], &output_rows[o]);
+        if baseline_row.len() != output_row.len() {
+            diff.missing_rows.push(baseline_row.clone());
+            diff.extra_rows.push(output_row.clone());
+            continue;
+        }
+        for (col, (b, o)) in baseline_row.iter().zip(output_row.iter()).enumerate() {
+            if !tolerances.get(col).unwrap_or(&Tolerance::EXACT).matches(b, o) {
+                diff.numeric_deltas.push(NumericDelta {
+                    row: baseline_row.clone(),
+                    column: header.get(col).cloned().unwrap_or_else(|| col.to_string()),
+                    baseline: b.parse().unwrap_or(f64::NAN),
+                    output: o.parse().unwrap_or(f64::NAN),
+                });
+            }
+        }
+    }
+    
+    diff.missing_rows.extend(
+        baseline_rows
+            .iter()
+            .zip(&baseline_used)
+            .filter(|(_, used)| !**used)
+            .map(|(row, _)| row.clone()),
+    );
+    diff
 }
 
 fn parse_csv(file: &Path) -> Result<(Vec<String>, Vec<Vec<String>>)> {
-    let content = fs::read_to_string(file)?;
-    let lines: Vec<&str> = content.lines().filter(|l| !l.trim().is_empty()).collect();
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_path(file)
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", file.display(), e))?;
     
-    if lines.is_empty() {
-        anyhow::bail!("Empty CSV file");
+    let header: Vec<String> = reader.headers()?.iter().map(|s| s.to_string()).collect();
+    if header.is_empty() {
+        anyhow::bail!("Empty CSV file: {}", file.display());
     }
     
-    let header = lines[0].split(',')
-        .map(|s| s.trim().to_string())
-        .collect();
-    
-    let data_rows: Vec<Vec<String>> = lines[1..].iter()
-        .map(|line| {
-            line.split(',')
-                .map(|s| s.trim().to_string())
-                .collect()
-        })
-        .collect();
+    let mut data_rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| anyhow::anyhow!("Malformed CSV in {}: {}", file.display(), e))?;
+        data_rows.push(record.iter().map(|s| s.to_string()).collect());
+    }
     
     Ok((header, data_rows))
 }
 
-fn rows_match_with_tolerance(row1: &Vec<String>, row2: &Vec<String>) -> bool {
+fn rows_match_with_tolerance(row1: &[String], row2: &[String], tolerances: &[Tolerance]) -> bool {
     if row1.len() != row2.len() {
         return false;
     }
     
-    for (cell1, cell2) in row1.iter().zip(row2.iter()) {
-        if !cells_match_with_tolerance(cell1, cell2) {
+    for ((cell1, cell2), tolerance) in row1.iter().zip(row2.iter()).zip(tolerances) {
+        if !tolerance.matches(cell1, cell2) {
             return false;
         }
     }
     
     true
 }
-
-fn cells_match_with_tolerance(cell1: &str, cell2: &str) -> bool {
-    if cell1 == cell2 {
-        return true;
-    }
-    
-    if let (Ok(val1), Ok(val2)) = (cell1.parse::<f64>(), cell2.parse::<f64>()) {
-        (val1 - val2).abs() < 0.1
-    } else {
-        false
-    }
-}
-
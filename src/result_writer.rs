@@ -0,0 +1,253 @@
+use anyhow::Result;
+use arrow::array::RecordBatch;
+use arrow::datatypes::{Field, Schema, SchemaRef};
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Output format for query results, selectable per run with `--output-format`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    Csv,
+    Json,
+    Ndjson,
+    Parquet,
+    Arrow,
+}
+
+impl OutputFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Csv => "csv",
+            OutputFormat::Json => "json",
+            OutputFormat::Ndjson => "ndjson",
+            OutputFormat::Parquet => "parquet",
+            OutputFormat::Arrow => "arrow",
+        }
+    }
+
+    pub fn writer(&self) -> Box<dyn ResultWriter> {
+        match self {
+            OutputFormat::Csv => Box::new(CsvResultWriter),
+            OutputFormat::Json => Box::new(JsonResultWriter { line_delimited: false }),
+            OutputFormat::Ndjson => Box::new(JsonResultWriter { line_delimited: true }),
+            OutputFormat::Parquet => Box::new(ParquetResultWriter),
+            OutputFormat::Arrow => Box::new(ArrowIpcResultWriter),
+        }
+    }
+}
+
+/// A sink that drains a query result into a file
+pub trait ResultWriter {
+    fn write(&self, rows: duckdb::Rows, out_path: &Path) -> Result<()>;
+}
+
+/// Fix column name normalization: DuckDB Rust bindings may return count(*) instead of count_star()
+pub fn normalize_column_name(name: &str) -> String {
+    if name == "count(*)" {
+        "count_star()".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// Writes every value as text, matching the baseline CSV files
+pub struct CsvResultWriter;
+
+impl ResultWriter for CsvResultWriter {
+    fn write(&self, mut rows: duckdb::Rows, out_path: &Path) -> Result<()> {
+        let mut file = File::create(out_path)?;
+        let mut wtr = csv::Writer::from_writer(&mut file);
+
+        let stmt_ref = rows.as_ref().ok_or_else(|| anyhow::anyhow!("Failed to get statement reference"))?;
+        let column_count = stmt_ref.column_count();
+        let columns: Vec<String> = (0..column_count)
+            .map(|i| stmt_ref.column_name(i).map(|s| normalize_column_name(s)))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        wtr.write_record(&columns)?;
+
+        while let Some(row) = rows.next()? {
+            let mut record = Vec::new();
+            for i in 0..column_count {
+                let value = extract_value_as_string(row, i);
+                record.push(value);
+            }
+            wtr.write_record(&record)?;
+        }
+
+        wtr.flush()?;
+
+        Ok(())
+    }
+}
+
+fn extract_value_as_string(row: &duckdb::Row, col_index: usize) -> String {
+    format_value(row.get_ref::<usize>(col_index).unwrap())
+}
+
+/// Text form of a value as it appears in result CSV files
+pub fn format_value(value: duckdb::types::ValueRef) -> String {
+    match value {
+        duckdb::types::ValueRef::Null => String::from("NULL"),
+        duckdb::types::ValueRef::Boolean(b) => b.to_string(),
+        duckdb::types::ValueRef::TinyInt(i) => i.to_string(),
+        duckdb::types::ValueRef::SmallInt(i) => i.to_string(),
+        duckdb::types::ValueRef::Int(i) => i.to_string(),
+        duckdb::types::ValueRef::BigInt(i) => i.to_string(),
+        duckdb::types::ValueRef::HugeInt(i) => i.to_string(),
+        duckdb::types::ValueRef::UTinyInt(u) => u.to_string(),
+        duckdb::types::ValueRef::USmallInt(u) => u.to_string(),
+        duckdb::types::ValueRef::UInt(u) => u.to_string(),
+        duckdb::types::ValueRef::UBigInt(u) => u.to_string(),
+        duckdb::types::ValueRef::Float(f) => trim_float(f as f64),
+        duckdb::types::ValueRef::Double(d) => trim_float(d),
+        duckdb::types::ValueRef::Decimal(d) => d.to_string(),
+        duckdb::types::ValueRef::Timestamp(_, ts) => format!("{}", ts),
+        duckdb::types::ValueRef::Text(bytes) => {
+            match std::str::from_utf8(bytes) {
+                Ok(s) => s.to_string(),
+                Err(_) => format!("{:?}", bytes),
+            }
+        },
+        duckdb::types::ValueRef::Blob(bytes) => format!("{:?}", bytes),
+        duckdb::types::ValueRef::Date32(i) => {
+            use chrono::{NaiveDate, Datelike};
+            let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+            if let Some(date) = epoch.checked_add_signed(chrono::Duration::days(i as i64)) {
+                format!("{:04}-{:02}-{:02}", date.year(), date.month(), date.day())
+            } else {
+                i.to_string()
+            }
+        },
+        duckdb::types::ValueRef::Time64(_, i) => i.to_string(),
+        duckdb::types::ValueRef::Interval { months, days, nanos } => format!("{}-{}-{}", months, days, nanos),
+        // Raw `events.type` is an ENUM; MV plans cast it to VARCHAR, so both must print the label
+        duckdb::types::ValueRef::Enum(..) => match duckdb::types::Value::from(value) {
+            duckdb::types::Value::Enum(label) => label,
+            _ => "<unsupported>".to_string(),
+        },
+        _ => "<unsupported>".to_string(),
+    }
+}
+
+fn trim_float(v: f64) -> String {
+    let s = v.to_string();
+    if s.contains('.') {
+        s.trim_end_matches('0').trim_end_matches('.').to_string()
+    } else {
+        s
+    }
+}
+
+/// Encode already-decoded rows as CSV, formatted the same way as `CsvResultWriter`
+pub fn write_csv_records(columns: &[String], records: &[Vec<duckdb::types::Value>], out: &mut dyn Write) -> Result<()> {
+    let mut wtr = csv::Writer::from_writer(out);
+    wtr.write_record(columns)?;
+    for record in records {
+        // Value -> ValueRef is not implemented for ENUM in duckdb-rs, so take the label directly
+        wtr.write_record(record.iter().map(|v| match v {
+            duckdb::types::Value::Enum(label) => label.clone(),
+            v => format_value(v.into()),
+        }))?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Drain the result as Arrow record batches, keeping DuckDB's native column types
+/// (dates, decimals, timestamps) and applying the same column name normalization as CSV.
+fn collect_batches(rows: &duckdb::Rows) -> Result<(SchemaRef, Vec<RecordBatch>)> {
+    let stmt = rows.as_ref().ok_or_else(|| anyhow::anyhow!("Failed to get statement reference"))?;
+
+    let normalize_schema = |schema: &Schema| -> SchemaRef {
+        let fields: Vec<Field> = schema
+            .fields()
+            .iter()
+            .map(|f| f.as_ref().clone().with_name(normalize_column_name(f.name())))
+            .collect();
+        Arc::new(Schema::new(fields))
+    };
+
+    let mut schema = normalize_schema(&stmt.schema());
+    let mut batches = Vec::new();
+    while let Some(struct_array) = stmt.step() {
+        let batch = RecordBatch::from(struct_array);
+        if batches.is_empty() {
+            schema = normalize_schema(&batch.schema());
+        }
+        batches.push(RecordBatch::try_new(schema.clone(), batch.columns().to_vec())?);
+    }
+
+    Ok((schema, batches))
+}
+
+/// JSON array of row objects, or one object per line when `line_delimited`
+pub struct JsonResultWriter {
+    line_delimited: bool,
+}
+
+impl ResultWriter for JsonResultWriter {
+    fn write(&self, rows: duckdb::Rows, out_path: &Path) -> Result<()> {
+        let (_schema, batches) = collect_batches(&rows)?;
+        let batch_refs: Vec<&RecordBatch> = batches.iter().collect();
+        let file = File::create(out_path)?;
+        let builder = arrow::json::WriterBuilder::new().with_explicit_nulls(true);
+
+        if self.line_delimited {
+            let mut writer = builder.build::<_, arrow::json::writer::LineDelimited>(file);
+            writer.write_batches(&batch_refs)?;
+            writer.finish()?;
+        } else {
+            let mut writer = builder.build::<_, arrow::json::writer::JsonArray>(file);
+            writer.write_batches(&batch_refs)?;
+            writer.finish()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parquet with ZSTD compression, same as the preprocessed events data
+pub struct ParquetResultWriter;
+
+impl ResultWriter for ParquetResultWriter {
+    fn write(&self, rows: duckdb::Rows, out_path: &Path) -> Result<()> {
+        use parquet::arrow::ArrowWriter;
+        use parquet::basic::{Compression, ZstdLevel};
+        use parquet::file::properties::WriterProperties;
+
+        let (schema, batches) = collect_batches(&rows)?;
+        let props = WriterProperties::builder()
+            .set_compression(Compression::ZSTD(ZstdLevel::default()))
+            .build();
+
+        let file = File::create(out_path)?;
+        let mut writer = ArrowWriter::try_new(file, schema, Some(props))?;
+        for batch in &batches {
+            writer.write(batch)?;
+        }
+        writer.close()?;
+
+        Ok(())
+    }
+}
+
+/// Arrow IPC file format
+pub struct ArrowIpcResultWriter;
+
+impl ResultWriter for ArrowIpcResultWriter {
+    fn write(&self, rows: duckdb::Rows, out_path: &Path) -> Result<()> {
+        let (schema, batches) = collect_batches(&rows)?;
+
+        let file = File::create(out_path)?;
+        let mut writer = arrow::ipc::writer::FileWriter::try_new(file, &schema)?;
+        for batch in &batches {
+            writer.write(batch)?;
+        }
+        writer.finish()?;
+
+        Ok(())
+    }
+}
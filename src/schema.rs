@@ -0,0 +1,251 @@
+use anyhow::Result;
+use duckdb::Connection;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::OnceLock;
+
+static EVENT_SCHEMA: OnceLock<EventSchema> = OnceLock::new();
+
+/// Table recording the schema a database was built with, read back by `--use-existing` runs
+const SCHEMA_TABLE: &str = "_calhacks_schema";
+
+/// Time buckets derived from the timestamp column for every dataset, as (name, SQL with `{ts}`)
+pub const DERIVED_TIME_COLUMNS: [(&str, &str); 4] = [
+    ("week", "DATE_TRUNC('week', {ts})"),
+    ("day", "DATE({ts})"),
+    ("hour", "DATE_TRUNC('hour', {ts})"),
+    ("minute", "STRFTIME({ts}, '%Y-%m-%d %H:%M')"),
+];
+
+/// Type a raw column is cast to when loading
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ColumnType {
+    Varchar,
+    Integer,
+    Bigint,
+    Double,
+    Timestamp,
+    Enum { values: Vec<String> },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColumnDef {
+    pub name: String,
+    #[serde(flatten)]
+    pub column_type: ColumnType,
+}
+
+/// How numeric values in the timestamp column are interpreted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampUnit {
+    S,
+    #[default]
+    Ms,
+    Us,
+}
+
+/// Columns of the raw event data and how the pipeline treats them. The loader, the derived
+/// time columns, MV registry validation and type partitioning all read from this.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventSchema {
+    /// Raw columns in file order
+    pub columns: Vec<ColumnDef>,
+    /// Event time column; `week`, `day`, `hour` and `minute` are derived from it
+    pub timestamp_column: String,
+    #[serde(default)]
+    pub timestamp_unit: TimestampUnit,
+    /// Enum column whose values each get their own partition of the large MVs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub partition_column: Option<String>,
+}
+
+impl EventSchema {
+    /// The ad events dataset this tool was built for
+    pub fn ad_events() -> Self {
+        let column = |name: &str, column_type: ColumnType| ColumnDef {
+            name: name.to_string(),
+            column_type,
+        };
+        let event_types = ["impression", "serve", "click", "purchase"];
+        Self {
+            columns: vec![
+                column("ts", ColumnType::Timestamp),
+                column(
+                    "type",
+                    ColumnType::Enum {
+                        values: event_types.iter().map(|s| s.to_string()).collect(),
+                    },
+                ),
+                column("auction_id", ColumnType::Varchar),
+                column("advertiser_id", ColumnType::Integer),
+                column("publisher_id", ColumnType::Integer),
+                column("bid_price", ColumnType::Double),
+                column("user_id", ColumnType::Bigint),
+                column("total_price", ColumnType::Double),
+                column("country", ColumnType::Varchar),
+            ],
+            timestamp_column: "ts".to_string(),
+            timestamp_unit: TimestampUnit::Ms,
+            partition_column: Some("type".to_string()),
+        }
+    }
+
+    /// JSON file in the same shape as `EventSchema`, e.g.
+    /// `{"columns": [{"name": "ts", "type": "timestamp"}, {"name": "device", "type": "enum", "values": ["ios", "android"]}], "timestamp_column": "ts", "partition_column": "device"}`
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Cannot read event schema {}: {}", path.display(), e))?;
+        let schema: Self = serde_json::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("Invalid event schema {}: {}", path.display(), e))?;
+        schema.validate()?;
+        Ok(schema)
+    }
+
+    /// Record this schema in the database being built
+    pub fn save_to_db(&self, con: &Connection) -> Result<()> {
+        con.execute(&format!("CREATE OR REPLACE TABLE {} (schema_json VARCHAR)", SCHEMA_TABLE), [])?;
+        con.execute(
+            &format!("INSERT INTO {} VALUES (?)", SCHEMA_TABLE),
+            [serde_json::to_string(self)?],
+        )?;
+        Ok(())
+    }
+
+    /// Schema recorded by `save_to_db`, or None for databases built before schemas were recorded
+    pub fn load_from_db(con: &Connection) -> Result<Option<Self>> {
+        let recorded: bool = con.query_row(
+            "SELECT COUNT(*) > 0 FROM information_schema.tables WHERE table_schema = 'main' AND table_name = ?",
+            [SCHEMA_TABLE],
+            |row| row.get(0),
+        )?;
+        if !recorded {
+            return Ok(None);
+        }
+        let json: String = con.query_row(&format!("SELECT schema_json FROM {}", SCHEMA_TABLE), [], |row| row.get(0))?;
+        let schema: Self = serde_json::from_str(&json)
+            .map_err(|e| anyhow::anyhow!("Invalid event schema recorded in the database: {}", e))?;
+        schema.validate()?;
+        Ok(Some(schema))
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        for (i, col) in self.columns.iter().enumerate() {
+            if col.name.is_empty() || !col.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                anyhow::bail!("Column name `{}` must be a plain identifier", col.name);
+            }
+            if self.columns[..i].iter().any(|c| c.name == col.name) {
+                anyhow::bail!("Column `{}` is defined twice", col.name);
+            }
+            if DERIVED_TIME_COLUMNS.iter().any(|(derived, _)| *derived == col.name) {
+                anyhow::bail!("Column `{}` clashes with a derived time column", col.name);
+            }
+            if let ColumnType::Enum { values } = &col.column_type {
+                if values.is_empty() {
+                    anyhow::bail!("Enum column `{}` has no values", col.name);
+                }
+                // Values are spliced into SQL string literals unescaped
+                if let Some(value) = values.iter().find(|v| v.is_empty() || v.contains('\'')) {
+                    anyhow::bail!("Enum column `{}` has value `{}`; values must be non-empty and free of quotes", col.name, value);
+                }
+            }
+        }
+
+        match self.column(&self.timestamp_column) {
+            Some(col) if col.column_type == ColumnType::Timestamp => {}
+            Some(_) => anyhow::bail!("Timestamp column `{}` must have type timestamp", self.timestamp_column),
+            None => anyhow::bail!("Timestamp column `{}` is not in the schema", self.timestamp_column),
+        }
+
+        if let Some(partition_column) = &self.partition_column {
+            match self.column(partition_column).map(|c| &c.column_type) {
+                // Each value names a partition table, `<mv>_<column>_<value>`
+                Some(ColumnType::Enum { values }) => {
+                    if let Some(value) = values.iter().find(|v| !v.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')) {
+                        anyhow::bail!(
+                            "Partition column `{}` has value `{}`; partition values must be plain identifiers",
+                            partition_column,
+                            value
+                        );
+                    }
+                }
+                Some(_) => anyhow::bail!("Partition column `{}` must be an enum", partition_column),
+                None => anyhow::bail!("Partition column `{}` is not in the schema", partition_column),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn column(&self, name: &str) -> Option<&ColumnDef> {
+        self.columns.iter().find(|c| c.name == name)
+    }
+
+    /// Raw or derived column that can appear in a query or an MV group-by
+    pub fn has_column(&self, name: &str) -> bool {
+        self.column(name).is_some() || DERIVED_TIME_COLUMNS.iter().any(|(derived, _)| *derived == name)
+    }
+
+    pub fn is_numeric(&self, name: &str) -> bool {
+        self.column(name).is_some_and(|c| {
+            matches!(c.column_type, ColumnType::Integer | ColumnType::Bigint | ColumnType::Double)
+        })
+    }
+
+    pub fn is_enum(&self, name: &str) -> bool {
+        self.column(name).is_some_and(|c| matches!(c.column_type, ColumnType::Enum { .. }))
+    }
+
+    /// Values of the partition column, or nothing when the schema has none
+    pub fn partition_values(&self) -> &[String] {
+        match self.partition_column.as_deref().and_then(|name| self.column(name)) {
+            Some(ColumnDef {
+                column_type: ColumnType::Enum { values },
+                ..
+            }) => values,
+            _ => &[],
+        }
+    }
+
+    /// SQL converting the VARCHAR raw column to its schema type
+    pub fn cast_expr(&self, col: &ColumnDef) -> String {
+        let name = &col.name;
+        match &col.column_type {
+            ColumnType::Varchar => name.clone(),
+            ColumnType::Integer => format!("TRY_CAST({} AS INTEGER)", name),
+            ColumnType::Bigint => format!("TRY_CAST({} AS BIGINT)", name),
+            ColumnType::Double => format!("TRY_CAST(NULLIF({}, '') AS DOUBLE)", name),
+            ColumnType::Timestamp => {
+                let per_second = match self.timestamp_unit {
+                    TimestampUnit::S => "1.0",
+                    TimestampUnit::Ms => "1000.0",
+                    TimestampUnit::Us => "1000000.0",
+                };
+                // Epoch numbers from CSV/NDJSON, or a timestamp string from typed sources
+                format!(
+                    "COALESCE(to_timestamp(TRY_CAST({0} AS DOUBLE) / {1}), TRY_CAST({0} AS TIMESTAMPTZ))",
+                    name, per_second
+                )
+            }
+            ColumnType::Enum { values } => format!(
+                "TRY_CAST({} AS ENUM({}))",
+                name,
+                values.iter().map(|v| format!("'{}'", v)).collect::<Vec<_>>().join(",")
+            ),
+        }
+    }
+}
+
+/// Use `schema` for this run. Must run before the first `get()`.
+pub fn init(schema: EventSchema) -> Result<&'static EventSchema> {
+    schema.validate()?;
+    if EVENT_SCHEMA.set(schema).is_err() {
+        anyhow::bail!("Event schema was already initialized");
+    }
+    Ok(get())
+}
+
+/// The active schema; the ad events schema unless `init` chose another
+pub fn get() -> &'static EventSchema {
+    EVENT_SCHEMA.get_or_init(EventSchema::ad_events)
+}
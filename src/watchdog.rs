@@ -0,0 +1,87 @@
+use duckdb::{Connection, InterruptHandle};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+#[derive(Default)]
+struct WatchState {
+    deadline: Option<Instant>,
+    fired: bool,
+    shutdown: bool,
+}
+
+/// Interrupts the query running on one connection once its deadline passes.
+///
+/// A single background thread per connection waits on a condvar, so arming and disarming
+/// around each execution costs a mutex round-trip rather than a thread spawn.
+pub struct QueryWatchdog {
+    state: Arc<(Mutex<WatchState>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl QueryWatchdog {
+    pub fn new(con: &Connection) -> Self {
+        let state = Arc::new((Mutex::new(WatchState::default()), Condvar::new()));
+        let interrupt = con.interrupt_handle();
+        let thread_state = state.clone();
+        let thread = std::thread::spawn(move || watch(&thread_state, &interrupt));
+
+        Self {
+            state,
+            thread: Some(thread),
+        }
+    }
+
+    /// Start the clock for the next query
+    pub fn arm(&self, timeout: Duration) {
+        let (lock, cvar) = &*self.state;
+        let mut state = lock.lock().unwrap();
+        state.deadline = Some(Instant::now() + timeout);
+        state.fired = false;
+        cvar.notify_one();
+    }
+
+    /// Stop the clock; returns true if the query was interrupted
+    pub fn disarm(&self) -> bool {
+        let (lock, _) = &*self.state;
+        let mut state = lock.lock().unwrap();
+        state.deadline = None;
+        std::mem::take(&mut state.fired)
+    }
+}
+
+impl Drop for QueryWatchdog {
+    fn drop(&mut self) {
+        {
+            let (lock, cvar) = &*self.state;
+            lock.lock().unwrap().shutdown = true;
+            cvar.notify_one();
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn watch(state: &(Mutex<WatchState>, Condvar), interrupt: &InterruptHandle) {
+    let (lock, cvar) = state;
+    let mut guard = lock.lock().unwrap();
+    loop {
+        if guard.shutdown {
+            return;
+        }
+        match guard.deadline {
+            None => guard = cvar.wait(guard).unwrap(),
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    interrupt.interrupt();
+                    guard.fired = true;
+                    guard.deadline = None;
+                } else {
+                    guard = cvar.wait_timeout(guard, deadline - now).unwrap().0;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,110 @@
+//! Shared fixture for the integration tests: a small generated dataset loaded the same way
+//! the binary loads real data, with base and type-partitioned MVs built on top.
+
+// Each test binary uses its own subset of the fixture
+#![allow(dead_code)]
+
+use calhacks::data_generator::{GeneratorConfig, generate_events};
+use calhacks::data_loader::load_data;
+use calhacks::data_quality::QualityOptions;
+use calhacks::hardware::{HardwareInfo, HardwareProfile};
+use calhacks::mv::MaterializedView;
+use calhacks::preprocessor::{
+    compute_mv_stats, create_materialized_views, create_type_partitioned_materialized_views, load_all_mvs_from_db,
+};
+use calhacks::query_handler::{parse_queries_from_file, predicate_column_pairs};
+use duckdb::Connection;
+use indicatif::ProgressBar;
+use serde_json::Value;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard, OnceLock, PoisonError};
+
+/// Small enough to build in a couple of seconds, large enough that every event type and
+/// every day shows up in each MV
+const FIXTURE_ROWS: usize = 100_000;
+const FIXTURE_PARTS: usize = 2;
+const FIXTURE_DAYS: usize = 14;
+const FIXTURE_SEED: u64 = 7;
+
+/// The production threshold would leave a fixture this small unpartitioned; this one still
+/// skips the tiny MVs (e.g. `mv_type_only`) so both paths are covered
+const FIXTURE_MIN_PARTITION_ROWS: i64 = 1_000;
+
+/// The planner's cost weights depend on the hardware; pin them so routing is the same on
+/// every machine
+const FIXTURE_HARDWARE: HardwareProfile = HardwareProfile {
+    threads: Some(8),
+    memory_gb: Some(16.0),
+    disk_read_mb_s: Some(2000.0),
+};
+
+pub struct Fixture {
+    /// Scratch directory holding the CSVs, the Parquet copy and per-test outputs
+    pub dir: PathBuf,
+    pub con: Mutex<Connection>,
+    /// Every MV found in the database, with stats, as the query path loads them
+    pub mvs: Mutex<Vec<MaterializedView>>,
+    pub queries: Vec<Value>,
+}
+
+impl Fixture {
+    /// A failed test must not poison the fixture for the tests after it
+    pub fn con(&self) -> MutexGuard<'_, Connection> {
+        self.con.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn mvs(&self) -> MutexGuard<'_, Vec<MaterializedView>> {
+        self.mvs.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Built once per test binary and shared by its tests
+pub fn fixture() -> &'static Fixture {
+    static FIXTURE: OnceLock<Fixture> = OnceLock::new();
+    FIXTURE.get_or_init(|| build_fixture().expect("failed to build test fixture"))
+}
+
+pub fn corpus_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/queries.json")
+}
+
+/// An empty `<tmp>/calhacks_<name>_<pid>`; tests in one binary run in parallel threads, so
+/// each must pass its own `name`
+pub fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("calhacks_{}_{}", name, std::process::id()));
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn build_fixture() -> anyhow::Result<Fixture> {
+    HardwareInfo::init(&FIXTURE_HARDWARE, None, None, false)?;
+
+    let dir = scratch_dir("fixture");
+    let data_dir = dir.join("data");
+    let config = GeneratorConfig::scaled(FIXTURE_ROWS, FIXTURE_PARTS, FIXTURE_SEED, FIXTURE_DAYS);
+    generate_events(&data_dir, &config, &ProgressBar::hidden())?;
+
+    let con = Connection::open_in_memory()?;
+    load_data(&con, &data_dir, None, None, &QualityOptions::default())?;
+    // The events view now reads the Parquet copy next to the CSVs, which are no longer needed
+    std::fs::remove_dir_all(&data_dir)?;
+
+    // Preprocessing, as in the binary: base MVs, then type partitions of the large ones
+    let base_mvs = create_materialized_views(&con, &ProgressBar::hidden())?;
+    create_type_partitioned_materialized_views(&con, &base_mvs, FIXTURE_MIN_PARTITION_ROWS)?;
+
+    // Query path: rediscover the MVs from the catalog and collect workload stats
+    let queries = parse_queries_from_file(&corpus_path())?;
+    let mut mvs = load_all_mvs_from_db(&con)?;
+    compute_mv_stats(&con, &mut mvs, &predicate_column_pairs(&queries))?;
+
+    Ok(Fixture {
+        dir,
+        con: Mutex::new(con),
+        mvs: Mutex::new(mvs),
+        queries,
+    })
+}
@@ -0,0 +1,89 @@
+//! The ingestion data-quality report over a small hand-written CSV with known defects.
+
+mod common;
+
+use calhacks::data_loader::load_data;
+use calhacks::data_quality::QualityOptions;
+use duckdb::Connection;
+use std::path::PathBuf;
+
+const HEADER: &str = "ts,type,auction_id,advertiser_id,publisher_id,bid_price,user_id,total_price,country";
+
+/// Five clean rows, then one defect each: an unparseable and a missing timestamp, a 1970 and
+/// a 2100 timestamp, an unknown type, a non-numeric advertiser id and a non-numeric bid price
+const ROWS: &[&str] = &[
+    "1717200000000,impression,a1,10,5,0.5,100,,US",
+    "1717200060000,click,a2,11,5,,101,,DE",
+    "1717200120000,purchase,a3,12,6,,102,42.5,US",
+    "1717200180000,serve,a4,10,7,,103,,JP",
+    "1717200240000,impression,a5,13,5,0.25,104,,FR",
+    "notatime,impression,a6,10,5,0.5,105,,US",
+    ",impression,a7,10,5,0.5,106,,US",
+    "1000,impression,a8,10,5,0.5,107,,US",
+    "4102444800000,click,a9,10,5,,108,,US",
+    "1717200300000,view,a10,10,5,,109,,US",
+    "1717200360000,impression,a11,ten,5,0.5,110,,US",
+    "1717200420000,impression,a12,10,5,abc,111,,US",
+];
+
+/// `data/events_part_0.csv` in a fresh scratch directory; the Parquet cache would go next to `data/`
+fn write_events(name: &str) -> PathBuf {
+    let data_dir = common::scratch_dir(name).join("data");
+    std::fs::create_dir_all(&data_dir).unwrap();
+    std::fs::write(data_dir.join("events_part_0.csv"), format!("{}\n{}\n", HEADER, ROWS.join("\n"))).unwrap();
+    data_dir
+}
+
+fn options() -> QualityOptions {
+    QualityOptions {
+        max_event_time: "2030-01-01 00:00:00".to_string(),
+        sample_rows: 3,
+        ..QualityOptions::default()
+    }
+}
+
+#[test]
+fn report_counts_each_kind_of_defect() {
+    let data_dir = write_events("quality_report");
+    let con = Connection::open_in_memory().unwrap();
+    let quality = load_data(&con, &data_dir, None, None, &options()).unwrap().quality.unwrap();
+
+    assert_eq!(quality.total_rows, 12);
+    assert_eq!(quality.bad_rows, 7);
+
+    let column = |name: &str| quality.columns.iter().find(|c| c.name == name).unwrap();
+    assert_eq!(column("ts").nulls, 1);
+    assert_eq!(column("ts").failed_casts, 1);
+    assert_eq!(column("ts").out_of_range, Some(2));
+    assert_eq!(column("type").unknown_values, vec![("view".to_string(), 1)]);
+    assert_eq!(column("advertiser_id").failed_casts, 1);
+    assert_eq!(column("bid_price").failed_casts, 1);
+    // Prices are empty by design for most event types; that is not a defect
+    assert_eq!(column("total_price").nulls, 11);
+    assert_eq!(column("total_price").failed_casts, 0);
+
+    assert_eq!(quality.samples.len(), 3);
+    for sample in &quality.samples {
+        assert!(!sample.issues.is_empty() && sample.issues.iter().all(|i| !i.is_empty()), "{:?}", sample);
+    }
+
+    std::fs::remove_dir_all(data_dir.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn too_many_bad_rows_fail_before_caching() {
+    let data_dir = write_events("quality_threshold");
+    let con = Connection::open_in_memory().unwrap();
+    let options = QualityOptions {
+        max_bad_rows_pct: Some(10.0),
+        ..options()
+    };
+
+    let err = load_data(&con, &data_dir, None, None, &options).unwrap_err().to_string();
+    assert!(err.contains("above --max-bad-rows-pct 10"), "{}", err);
+    assert!(err.contains("type: 0 null, 1 failed casts; unknown values: view (1)"), "{}", err);
+    // A later run must not silently reuse a cache of the rejected data
+    assert!(!data_dir.parent().unwrap().join("events.parquet").exists());
+
+    std::fs::remove_dir_all(data_dir.parent().unwrap()).unwrap();
+}
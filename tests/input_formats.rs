@@ -0,0 +1,105 @@
+//! The same events written as CSV, gzip CSV, NDJSON and Parquet must load into identical
+//! `events` views.
+
+mod common;
+
+use calhacks::data_loader::{InputFormat, load_data};
+use calhacks::data_quality::QualityOptions;
+use duckdb::Connection;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+const CSV: &str = "\
+ts,type,auction_id,advertiser_id,publisher_id,bid_price,user_id,total_price,country
+1717200000000,impression,a1,10,5,0.5,100,,US
+1717203600000,click,a2,11,5,,101,,DE
+1717290000000,purchase,a3,12,6,,102,42.5,US
+1717293600000,serve,a4,10,7,,103,,JP
+1717380000000,impression,a5,13,5,0.25,104,,FR
+";
+
+/// Typed values of every column, in a fixed row order, so formats can be compared directly
+const FINGERPRINT_SQL: &str = "SELECT
+    COUNT(*)::VARCHAR,
+    STRING_AGG(CAST(ts AS VARCHAR), ',' ORDER BY auction_id),
+    STRING_AGG(CAST(type AS VARCHAR), ',' ORDER BY auction_id),
+    STRING_AGG(CAST(day AS VARCHAR), ',' ORDER BY auction_id),
+    SUM(advertiser_id)::VARCHAR,
+    SUM(publisher_id)::VARCHAR,
+    SUM(user_id)::VARCHAR,
+    SUM(bid_price)::VARCHAR,
+    SUM(total_price)::VARCHAR,
+    STRING_AGG(country, ',' ORDER BY auction_id)
+FROM events";
+
+/// `data/` in a fresh scratch directory, with the Parquet cache going next to it
+fn data_dir(name: &str) -> PathBuf {
+    let data_dir = common::scratch_dir(&format!("input_{}", name)).join("data");
+    std::fs::create_dir_all(&data_dir).unwrap();
+    data_dir
+}
+
+/// Write the CSV rows into `file` with DuckDB's COPY options, e.g. `FORMAT PARQUET`
+fn convert_csv(data_dir: &Path, file: &str, copy_options: &str) {
+    let csv = data_dir.join("source.csv.tmp");
+    std::fs::write(&csv, CSV).unwrap();
+    let con = Connection::open_in_memory().unwrap();
+    con.execute(
+        &format!(
+            "COPY (SELECT * FROM read_csv('{}', HEADER = TRUE)) TO '{}' ({})",
+            csv.display(),
+            data_dir.join(file).display(),
+            copy_options
+        ),
+        [],
+    )
+    .unwrap();
+    std::fs::remove_file(&csv).unwrap();
+}
+
+fn load_fingerprint(data_dir: &Path, format: Option<InputFormat>) -> Vec<Option<String>> {
+    let con = Connection::open_in_memory().unwrap();
+    load_data(&con, data_dir, format, None, &QualityOptions::default()).unwrap();
+    let fingerprint = con
+        .query_row(FINGERPRINT_SQL, [], |row| {
+            (0..10).map(|i| row.get::<_, Option<String>>(i)).collect::<Result<Vec<_>, _>>()
+        })
+        .unwrap();
+    std::fs::remove_dir_all(data_dir.parent().unwrap()).unwrap();
+    fingerprint
+}
+
+/// Loaded once and shared, so parallel tests never touch the same CSV directory
+fn csv_fingerprint() -> &'static [Option<String>] {
+    static FINGERPRINT: OnceLock<Vec<Option<String>>> = OnceLock::new();
+    FINGERPRINT.get_or_init(|| {
+        let dir = data_dir("csv");
+        std::fs::write(dir.join("events_part_0.csv"), CSV).unwrap();
+        load_fingerprint(&dir, Some(InputFormat::Csv))
+    })
+}
+
+fn assert_matches_csv(name: &str, file: &str, copy_options: &str) {
+    let expected = csv_fingerprint();
+    assert_eq!(expected[0].as_deref(), Some("5"));
+
+    let dir = data_dir(name);
+    convert_csv(&dir, file, copy_options);
+    // Detected from the file name, as without --input-format
+    assert_eq!(load_fingerprint(&dir, None), expected, "{} loads differently from CSV", name);
+}
+
+#[test]
+fn gzip_csv_loads_like_csv() {
+    assert_matches_csv("csv_gz", "events_part_0.csv.gz", "FORMAT CSV, HEADER, COMPRESSION GZIP");
+}
+
+#[test]
+fn ndjson_loads_like_csv() {
+    assert_matches_csv("ndjson", "events_part_0.ndjson", "FORMAT JSON");
+}
+
+#[test]
+fn parquet_loads_like_csv() {
+    assert_matches_csv("parquet", "events_part_0.parquet", "FORMAT PARQUET");
+}
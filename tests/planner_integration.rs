@@ -0,0 +1,130 @@
+//! End-to-end checks over the generated fixture: the planner routes each corpus query to the
+//! expected MV, and the MV plan returns the same rows as plain SQL over raw events.
+
+mod common;
+
+use calhacks::planner::Planner;
+use calhacks::query_handler::assemble_sql;
+use calhacks::result_checker::{Tolerance, ToleranceConfig, compare_csv_files};
+use calhacks::result_writer::OutputFormat;
+use calhacks::query_executor::write_single_result;
+use duckdb::Connection;
+use std::path::Path;
+
+/// Chosen MV per corpus query, in corpus order; None means the query falls back to raw events
+const EXPECTED_MVS: &[Option<&str>] = &[
+    Some("mv_type_day_country_type_impression"),
+    Some("mv_type_day_country_type_purchase"),
+    Some("mv_type_day_country_publisher_id_type_impression"),
+    Some("mv_type_day_minute_type_impression"),
+    Some("mv_advertiser_id_fast"),
+    Some("mv_type_only"),
+    Some("mv_type_week"),
+    Some("mv_advertiser_id_full_type_impression"),
+    None,
+    None,
+];
+
+fn run_to_csv(con: &Connection, sql: &str, query_num: usize, dir: &Path) -> anyhow::Result<()> {
+    let mut stmt = con.prepare(sql)?;
+    let rows = stmt.query([])?;
+    write_single_result(query_num, rows, &dir.to_path_buf(), OutputFormat::Csv)
+}
+
+#[test]
+fn type_partitions_are_rediscovered() {
+    let fixture = common::fixture();
+    let mvs = fixture.mvs();
+
+    let partitioned: Vec<_> = mvs.iter().filter(|mv| mv.partition_value().is_some()).collect();
+    assert!(!partitioned.is_empty(), "no type-partitioned MVs were created");
+    for mv in partitioned {
+        assert!(
+            !mv.group_by.iter().any(|c| c == "type"),
+            "{} still groups by type: {:?}",
+            mv.name,
+            mv.group_by
+        );
+    }
+    assert!(
+        !mvs.iter().any(|mv| mv.name.starts_with("mv_type_only_type_")),
+        "tiny MVs below the partition threshold should not be partitioned"
+    );
+}
+
+#[test]
+fn partitions_hold_the_same_events_as_their_base_mv() {
+    let fixture = common::fixture();
+    let con = fixture.con();
+    let mvs = fixture.mvs();
+
+    for mv in mvs.iter() {
+        let Some(event_type) = mv.partition_value() else {
+            continue;
+        };
+        let base = mv.name.strip_suffix(&format!("_type_{}", event_type)).unwrap();
+        let (base_rows, partition_rows): (i64, i64) = con
+            .query_row(
+                &format!(
+                    "SELECT (SELECT SUM(count_rows) FROM {} WHERE type = '{}'), (SELECT SUM(count_rows) FROM {})",
+                    base, event_type, mv.name
+                ),
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(base_rows, partition_rows, "{} does not hold the {} events of {}", mv.name, event_type, base);
+    }
+}
+
+#[test]
+fn planner_chooses_expected_mvs() {
+    let fixture = common::fixture();
+    let con = fixture.con();
+    let mvs = fixture.mvs();
+    let planner = Planner::new(&con);
+    assert_eq!(fixture.queries.len(), EXPECTED_MVS.len(), "EXPECTED_MVS is out of sync with the corpus");
+
+    let mut mismatches = Vec::new();
+    for (i, (query, expected)) in fixture.queries.iter().zip(EXPECTED_MVS).enumerate() {
+        let chosen = planner.choose_mv(query, &mvs).map(|idx| mvs[idx].name.as_str());
+        if chosen != *expected {
+            mismatches.push(format!("Q{}: expected {:?}, chose {:?}", i + 1, expected, chosen));
+        }
+    }
+    assert!(mismatches.is_empty(), "planner routing changed:\n{}", mismatches.join("\n"));
+}
+
+#[test]
+fn mv_plans_match_raw_baseline() {
+    let fixture = common::fixture();
+    let con = fixture.con();
+    let mut mvs = fixture.mvs();
+    let planner = Planner::new(&con);
+    // Stricter than the CLI default: MV rollups must reproduce raw aggregates up to summation order
+    let tolerances = ToleranceConfig {
+        default: Tolerance { abs: 1e-9, rel: 1e-9, ulps: 4 },
+        ..ToleranceConfig::default()
+    };
+
+    let baseline_dir = fixture.dir.join("baseline");
+    let output_dir = fixture.dir.join("output");
+    let mut failures = Vec::new();
+    for (i, query) in fixture.queries.iter().enumerate() {
+        let query_num = i + 1;
+        run_to_csv(&con, &assemble_sql(query), query_num, &baseline_dir).unwrap();
+
+        let sql = planner.translate_query(query, &mut mvs, false).unwrap();
+        if let Err(e) = run_to_csv(&con, &sql, query_num, &output_dir) {
+            failures.push(format!("Q{}: `{}` failed: {}", query_num, sql, e));
+            continue;
+        }
+
+        let file = format!("q{}.csv", query_num);
+        let diff = compare_csv_files(&baseline_dir.join(&file), &output_dir.join(&file), Some(query), &tolerances).unwrap();
+        if !diff.is_match() {
+            failures.push(format!("Q{}: {} (`{}`)", query_num, diff.summary(), sql));
+        }
+    }
+    assert!(failures.is_empty(), "MV results differ from raw events:\n{}", failures.join("\n"));
+}
@@ -0,0 +1,132 @@
+//! Golden snapshots of what the planner emits for each corpus query: the chosen MV and the
+//! SQL from `translate_query`. Any change fails with a line diff against the stored snapshot.
+//!
+//! To accept the new output after an intended planner change:
+//!
+//!     UPDATE_SNAPSHOTS=1 cargo test --test planner_snapshots
+//!
+//! then review and commit the changed files under `tests/snapshots/planner/`.
+
+mod common;
+
+use calhacks::planner::Planner;
+use std::path::PathBuf;
+
+const UPDATE_ENV: &str = "UPDATE_SNAPSHOTS";
+
+fn snapshot_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots/planner")
+}
+
+/// Put each clause on its own line so a diff points at the clause that changed
+fn format_sql(sql: &str) -> String {
+    let mut out = sql.to_string();
+    for keyword in [" FROM ", " WHERE ", " GROUP BY ", " ORDER BY ", " LIMIT "] {
+        out = out.replace(keyword, &format!("\n{}", &keyword[1..]));
+    }
+    out.replace(" AND ", "\n  AND ")
+}
+
+fn render_snapshot(query: &serde_json::Value, chosen_mv: Option<&str>, sql: &str) -> String {
+    format!(
+        "query: {}\nmv: {}\nsql:\n{}\n",
+        query,
+        chosen_mv.unwrap_or("<raw events>"),
+        format_sql(sql)
+    )
+}
+
+/// Line diff of `old` against `new` (longest common subsequence), `-`/`+` marking changed lines
+fn diff_lines(old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // lcs[i][j] = length of the LCS of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            out.push(format!("  {}", old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            out.push(format!("- {}", old[i]));
+            i += 1;
+        } else {
+            out.push(format!("+ {}", new[j]));
+            j += 1;
+        }
+    }
+    out.join("\n")
+}
+
+#[test]
+fn planner_sql_matches_snapshots() {
+    let fixture = common::fixture();
+    let con = fixture.con();
+    let mut mvs = fixture.mvs();
+    let planner = Planner::new(&con);
+    let update = std::env::var_os(UPDATE_ENV).is_some();
+
+    let dir = snapshot_dir();
+    if update {
+        std::fs::create_dir_all(&dir).unwrap();
+    }
+
+    let mut failures = Vec::new();
+    for (i, query) in fixture.queries.iter().enumerate() {
+        let chosen = planner.choose_mv(query, &mvs).map(|idx| mvs[idx].name.clone());
+        let sql = planner.translate_query(query, &mut mvs, false).unwrap();
+        let actual = render_snapshot(query, chosen.as_deref(), &sql);
+
+        let path = dir.join(format!("q{:02}.snap", i + 1));
+        if update {
+            std::fs::write(&path, &actual).unwrap();
+            continue;
+        }
+        match std::fs::read_to_string(&path) {
+            Ok(expected) if expected == actual => {}
+            Ok(expected) => failures.push(format!(
+                "Q{} differs from {} (- snapshot, + planner output):\n{}",
+                i + 1,
+                path.display(),
+                diff_lines(&expected, &actual)
+            )),
+            Err(_) => failures.push(format!("Q{}: no snapshot at {}", i + 1, path.display())),
+        }
+    }
+
+    // Snapshots left over from queries that were removed from the corpus
+    if let Ok(entries) = std::fs::read_dir(&dir) {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let index = name.strip_prefix('q').and_then(|n| n.strip_suffix(".snap")).and_then(|n| n.parse::<usize>().ok());
+            if !index.is_some_and(|n| n == 0 || n > fixture.queries.len()) {
+                continue;
+            }
+            if update {
+                std::fs::remove_file(entry.path()).unwrap();
+            } else {
+                failures.push(format!("stale snapshot {} has no corpus query", name));
+            }
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{}\n\nIf these changes are intended, accept them with `{}=1 cargo test --test planner_snapshots`",
+        failures.join("\n\n"),
+        UPDATE_ENV
+    );
+}